@@ -0,0 +1,350 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{BenchData, BenchResult, CargoUI};
+use anyhow::Context;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// A change of the time of a benchmark above this ratio is reported
+const THRESHOLD: f64 = 0.05;
+
+/// The time of one iteration of a benchmark
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub name: String,
+    pub nanoseconds: f64,
+}
+
+/// Parse the output of `cargo bench`, both from the libtest harness and from criterion
+#[derive(Default)]
+pub struct BenchOutputParser {
+    /// criterion prints long benchmark names on their own line, before the time
+    previous_line: String,
+}
+
+impl BenchOutputParser {
+    pub fn parse_line(&mut self, line: &str) -> Option<Measurement> {
+        let result = parse_libtest(line).or_else(|| self.parse_criterion(line));
+        if !line.trim().is_empty() {
+            self.previous_line = line.trim().to_owned();
+        }
+        result
+    }
+
+    /// `fib 20                  time:   [26.029 µs 26.251 µs 26.505 µs]`
+    fn parse_criterion(&self, line: &str) -> Option<Measurement> {
+        let (name, times) = line.split_once("time:")?;
+        let name = match name.trim() {
+            "" => self.previous_line.as_str(),
+            name => name,
+        };
+        let times: Vec<&str> = times
+            .trim()
+            .strip_prefix('[')?
+            .strip_suffix(']')?
+            .split_whitespace()
+            .collect();
+        // lower bound, estimate, and upper bound, each with a unit
+        let [_, _, estimate, unit, _, _] = times.as_slice() else {
+            return None;
+        };
+        Some(Measurement {
+            name: name.to_owned(),
+            nanoseconds: estimate.parse::<f64>().ok()? * unit_in_ns(unit)?,
+        })
+    }
+}
+
+/// `test bench_add ... bench:       1,234.50 ns/iter (+/- 56)`
+fn parse_libtest(line: &str) -> Option<Measurement> {
+    let (name, result) = line.strip_prefix("test ")?.split_once(" ... bench:")?;
+    let (value, unit) = result.trim().split_once(' ')?;
+    let unit = unit.split_whitespace().next()?.strip_suffix("/iter")?;
+    Some(Measurement {
+        name: name.trim().to_owned(),
+        nanoseconds: value.replace(',', "").parse::<f64>().ok()? * unit_in_ns(unit)?,
+    })
+}
+
+fn unit_in_ns(unit: &str) -> Option<f64> {
+    Some(match unit {
+        "ps" => 0.001,
+        "ns" => 1.,
+        "µs" | "us" => 1_000.,
+        "ms" => 1_000_000.,
+        "s" => 1_000_000_000.,
+        _ => return None,
+    })
+}
+
+fn format_time(nanoseconds: f64) -> String {
+    let (value, unit) = if nanoseconds >= 1_000_000_000. {
+        (nanoseconds / 1_000_000_000., "s")
+    } else if nanoseconds >= 1_000_000. {
+        (nanoseconds / 1_000_000., "ms")
+    } else if nanoseconds >= 1_000. {
+        (nanoseconds / 1_000., "µs")
+    } else {
+        (nanoseconds, "ns")
+    };
+    format!("{:.2} {}", value, unit)
+}
+
+/// Show a benchmark in the table while the benchmarks are running
+pub fn push_measurement(ui: &CargoUI, measurement: &Measurement) {
+    let model_handle = ui.global::<BenchData>().get_results();
+    let Some(model) = model_handle
+        .as_any()
+        .downcast_ref::<VecModel<BenchResult>>()
+    else {
+        return;
+    };
+    model.push(BenchResult {
+        name: measurement.name.as_str().into(),
+        time: format_time(measurement.nanoseconds).into(),
+        ..Default::default()
+    });
+}
+
+/// Empty the table, before the benchmarks are run
+pub fn reset(ui: &CargoUI) {
+    let data = ui.global::<BenchData>();
+    data.set_results(ModelRc::from(
+        Rc::new(VecModel::<BenchResult>::default()) as Rc<dyn Model<Data = BenchResult>>
+    ));
+    data.set_status("Running the benchmarks...".into());
+}
+
+/// The results of a `cargo bench`, as stored in `target/cargo-ui/bench/<commit>.json`
+struct BenchRun {
+    commit: String,
+    timestamp: u64,
+    results: HashMap<String, f64>,
+}
+
+impl BenchRun {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to load '{}'", path.display()))?;
+        let value: serde_json::Value = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        Ok(Self {
+            commit: value["commit"].as_str().unwrap_or_default().to_owned(),
+            timestamp: value["timestamp"].as_u64().unwrap_or_default(),
+            results: value["results"]
+                .as_object()
+                .map(|r| {
+                    r.iter()
+                        .filter_map(|(name, ns)| Some((name.clone(), ns.as_f64()?)))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::json!({
+            "commit": self.commit,
+            "timestamp": self.timestamp,
+            "results": self.results,
+        });
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&json)?)
+            .with_context(|| format!("Failed to write '{}'", path.display()))
+    }
+
+    /// Keep the results of an older run of the same commit for the benchmarks that were not run
+    /// again, e.g. when only one bench target was run
+    fn merge(&mut self, older: BenchRun) {
+        for (name, nanoseconds) in older.results {
+            self.results.entry(name).or_insert(nanoseconds);
+        }
+    }
+}
+
+/// The commit checked out in the workspace, with a `-dirty` suffix if there are local changes
+async fn current_commit(workspace_root: &Path) -> String {
+    let git = |args: &'static [&'static str]| async move {
+        tokio::process::Command::new("git")
+            .args(args)
+            .current_dir(workspace_root)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
+    };
+    let Some(commit) = git(&["rev-parse", "--short", "HEAD"]).await else {
+        return "no-commit".into();
+    };
+    if git(&["status", "--porcelain"])
+        .await
+        .is_some_and(|s| !s.is_empty())
+    {
+        format!("{}-dirty", commit)
+    } else {
+        commit
+    }
+}
+
+/// The run that was stored last
+fn latest_run(directory: &Path) -> Option<BenchRun> {
+    std::fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| BenchRun::load(&entry.ok()?.path()).ok())
+        .max_by_key(|run| run.timestamp)
+}
+
+/// Store the results of the benchmarks for the current commit, and show them compared to the
+/// previous run
+pub async fn record(
+    measurements: Vec<Measurement>,
+    workspace_root: &Path,
+    target_directory: PathBuf,
+    handle: slint::Weak<CargoUI>,
+) {
+    let directory = target_directory.join("cargo-ui").join("bench");
+    let previous = latest_run(&directory);
+    let mut run = BenchRun {
+        commit: current_commit(workspace_root).await,
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        results: measurements
+            .iter()
+            .map(|m| (m.name.clone(), m.nanoseconds))
+            .collect(),
+    };
+    let path = directory.join(format!("{}.json", run.commit));
+    if let Ok(older) = BenchRun::load(&path) {
+        run.merge(older);
+    }
+    let saved = run.save(&path);
+
+    let results: Vec<BenchResult> = measurements
+        .iter()
+        .map(|m| {
+            let mut result = BenchResult {
+                name: m.name.as_str().into(),
+                time: format_time(m.nanoseconds).into(),
+                ..Default::default()
+            };
+            if let Some(before) = previous.as_ref().and_then(|p| p.results.get(&m.name)) {
+                let change = m.nanoseconds / before - 1.;
+                result.previous = format_time(*before).into();
+                result.change = format!("{:+.1}%", change * 100.).into();
+                result.status = if change > THRESHOLD {
+                    1
+                } else if change < -THRESHOLD {
+                    2
+                } else {
+                    3
+                };
+            }
+            result
+        })
+        .collect();
+    let regressions = results.iter().filter(|r| r.status == 1).count();
+    let status: SharedString = match (&saved, &previous) {
+        (Err(e), _) => format!("{:#}", e).into(),
+        (Ok(()), Some(previous)) => format!(
+            "Compared to the previous run, on commit {}: {} regressions",
+            previous.commit, regressions
+        )
+        .into(),
+        (Ok(()), None) => format!("Results saved for commit {}", run.commit).into(),
+    };
+
+    handle
+        .upgrade_in_event_loop(move |ui| {
+            let data = ui.global::<BenchData>();
+            data.set_results(ModelRc::from(
+                Rc::new(VecModel::from(results)) as Rc<dyn Model<Data = BenchResult>>
+            ));
+            data.set_status(status);
+        })
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(lines: &[&str]) -> Vec<(String, f64)> {
+        let mut parser = BenchOutputParser::default();
+        lines
+            .iter()
+            .filter_map(|line| parser.parse_line(line))
+            .map(|m| (m.name, m.nanoseconds))
+            .collect()
+    }
+
+    #[test]
+    fn libtest_output() {
+        assert_eq!(
+            parse(&[
+                "running 2 tests",
+                "test bench_add ... bench:       1,234.50 ns/iter (+/- 56)",
+                "test bench_slow  ... bench:  2 ms/iter (+/- 1)",
+                "test not_a_bench ... ok",
+            ]),
+            [
+                ("bench_add".to_owned(), 1234.5),
+                ("bench_slow".to_owned(), 2_000_000.)
+            ]
+        );
+    }
+
+    #[test]
+    fn criterion_output() {
+        assert_eq!(
+            parse(&[
+                "Benchmarking fib 20: Analyzing",
+                "fib 20                  time:   [26.029 µs 26.251 µs 26.505 µs]",
+                "                        change: [-1.2% +0.4% +2.1%] (p = 0.62 > 0.05)",
+                "group/a very long benchmark name",
+                "                        time:   [1.5000 ms 1.5100 ms 1.5200 ms]",
+                "Found 3 outliers among 100 measurements (3.00%)",
+                "tiny                    time:   [120.00 ps 125.00 ps 130.00 ps]",
+            ]),
+            [
+                ("fib 20".to_owned(), 26251.),
+                ("group/a very long benchmark name".to_owned(), 1_510_000.),
+                ("tiny".to_owned(), 0.125),
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_run_keeps_the_other_results() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("abc1234.json");
+        let full = BenchRun {
+            commit: "abc1234".into(),
+            timestamp: 1,
+            results: [("a".to_owned(), 10.), ("b".to_owned(), 20.)].into(),
+        };
+        full.save(&path).unwrap();
+        let mut partial = BenchRun {
+            commit: "abc1234".into(),
+            timestamp: 2,
+            results: [("b".to_owned(), 25.)].into(),
+        };
+        partial.merge(BenchRun::load(&path).unwrap());
+        partial.save(&path).unwrap();
+
+        let stored = BenchRun::load(&path).unwrap();
+        assert_eq!(stored.timestamp, 2);
+        assert_eq!(stored.results.len(), 2);
+        assert_eq!(stored.results["a"], 10.);
+        assert_eq!(stored.results["b"], 25.);
+    }
+}
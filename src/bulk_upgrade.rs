@@ -0,0 +1,230 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{BulkUpgradeData, CargoUI, UpgradeCandidate};
+use crate::cargo::{cargo_command, set_dependency_version};
+use crate::dependency_features::resolved_package;
+use crate::dependency_versions::upgrades;
+use crate::manifest_edits::{is_from_registry, ManifestEdits};
+use anyhow::Context;
+use cargo_metadata::{DependencyKind, Metadata};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The files changed by an upgrade, with their previous content (None if they did not exist)
+pub type Backup = Vec<(PathBuf, Option<String>)>;
+
+/// Open the upgrade dialog with the outdated dependencies from the registry of all the workspace
+/// members
+pub fn list(metadata: &Metadata, index: &crates_index::GitIndex, handle: slint::Weak<CargoUI>) {
+    let mut candidates = Vec::new();
+    for package in metadata.workspace_packages() {
+        for dependency in &package.dependencies {
+            if !is_from_registry(dependency) {
+                continue;
+            }
+            let Ok(resolved) =
+                resolved_package(metadata, package, &dependency.name, dependency.kind)
+            else {
+                continue;
+            };
+            let Some(krate) = index.crate_(&dependency.name) else {
+                continue;
+            };
+            let (compatible, incompatible) = upgrades(&krate, &resolved.version, &dependency.req);
+            if compatible.is_none() && incompatible.is_none() {
+                continue;
+            }
+            let version = |v: Option<cargo_metadata::semver::Version>| {
+                v.map(|v| v.to_string().into()).unwrap_or_default()
+            };
+            candidates.push(UpgradeCandidate {
+                package: package.name.as_str().into(),
+                manifest: package.manifest_path.as_str().into(),
+                key: dependency
+                    .rename
+                    .as_deref()
+                    .unwrap_or(&dependency.name)
+                    .into(),
+                crate_name: dependency.name.as_str().into(),
+                dep_kind: match dependency.kind {
+                    DependencyKind::Normal => String::new(),
+                    kind => kind.to_string(),
+                }
+                .into(),
+                target: dependency
+                    .target
+                    .as_ref()
+                    .map(|t| t.to_string())
+                    .unwrap_or_default()
+                    .into(),
+                requirement: dependency.req.to_string().into(),
+                locked: resolved.version.to_string().into(),
+                latest_compatible: version(compatible),
+                latest_incompatible: version(incompatible),
+                selected: true,
+            });
+        }
+    }
+
+    handle
+        .upgrade_in_event_loop(move |h| {
+            let data = h.global::<BulkUpgradeData>();
+            data.set_candidates(ModelRc::from(
+                Rc::new(VecModel::from(candidates)) as Rc<dyn Model<Data = UpgradeCandidate>>
+            ));
+            data.set_message(Default::default());
+            data.set_can_rollback(false);
+            data.set_visible(true);
+        })
+        .unwrap();
+}
+
+/// The requirement written for the candidate: the most recent version, if allowed
+pub fn new_version(candidate: &UpgradeCandidate, allow_breaking: bool) -> SharedString {
+    if allow_breaking && !candidate.latest_incompatible.is_empty() {
+        candidate.latest_incompatible.clone()
+    } else {
+        candidate.latest_compatible.clone()
+    }
+}
+
+/// Write the new requirements in the manifests, each manifest being written once, and return the
+/// previous content of the manifests and of the lockfile. The requirements of the inherited
+/// dependencies are changed in `[workspace.dependencies]`.
+pub fn apply(
+    metadata: &Metadata,
+    candidates: &[UpgradeCandidate],
+    allow_breaking: bool,
+) -> anyhow::Result<Backup> {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let mut edits = ManifestEdits::default();
+    for candidate in candidates {
+        let manifest = Path::new(candidate.manifest.as_str());
+        let target = Some(candidate.target.as_str()).filter(|t| !t.is_empty());
+        let dep = edits.dependency(
+            manifest,
+            workspace_root,
+            crate::dep_kind_from_str(candidate.dep_kind.clone()),
+            target,
+            &candidate.key,
+        )?;
+        set_dependency_version(dep, &new_version(candidate, allow_breaking)).with_context(
+            || {
+                format!(
+                    "Failed to upgrade '{}' in '{}'",
+                    candidate.key,
+                    manifest.display()
+                )
+            },
+        )?;
+    }
+    let lockfile = workspace_root.join("Cargo.lock");
+    let lockfile_contents = std::fs::read_to_string(&lockfile).ok();
+    // Nothing is written before all the edits are known to be possible
+    let written = edits.write()?;
+    Ok(std::iter::once((lockfile, lockfile_contents))
+        .chain(
+            written
+                .into_iter()
+                .map(|(path, contents)| (path, Some(contents))),
+        )
+        .collect())
+}
+
+/// Write back the files as they were before the upgrade. The other files are still restored
+/// when one of them fails.
+pub fn restore(backup: &Backup) -> anyhow::Result<()> {
+    let mut failures = Vec::new();
+    for (path, contents) in backup {
+        let result = match contents {
+            Some(contents) => std::fs::write(path, contents),
+            None => std::fs::remove_file(path),
+        };
+        match result {
+            // A lockfile that did not exist before the upgrade may still not exist
+            Err(e) if contents.is_some() || e.kind() != std::io::ErrorKind::NotFound => {
+                failures.push(format!("'{}': {}", path.display(), e))
+            }
+            _ => (),
+        }
+    }
+    if !failures.is_empty() {
+        anyhow::bail!("Failed to restore {}", failures.join(", "));
+    }
+    Ok(())
+}
+
+/// Run `cargo check` on the upgraded workspace, and show the result in the dialog. Returns whether
+/// it succeeded.
+pub async fn check(
+    manifest_path: PathBuf,
+    toolchain: SharedString,
+    handle: slint::Weak<CargoUI>,
+) -> bool {
+    handle
+        .clone()
+        .upgrade_in_event_loop(|h| {
+            let data = h.global::<BulkUpgradeData>();
+            data.set_busy(true);
+            data.set_message("Running cargo check...".into());
+        })
+        .unwrap();
+    let mut cmd = cargo_command(&toolchain);
+    cmd.args(["check", "--workspace", "--all-targets", "--manifest-path"])
+        .arg(&manifest_path)
+        .kill_on_drop(true);
+    if let Some(directory) = manifest_path.parent() {
+        cmd.current_dir(directory);
+    }
+    let (success, message) = match cmd.output().await {
+        Ok(output) if output.status.success() => (
+            true,
+            "The upgraded workspace builds with cargo check".to_owned(),
+        ),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // the errors are at the end, after the list of the compiled crates
+            let lines = stderr.lines().collect::<Vec<_>>();
+            let errors = lines[lines.len().saturating_sub(30)..].join("\n");
+            (false, format!("cargo check failed:\n{}", errors))
+        }
+        Err(e) => (false, format!("Failed to run cargo check: {}", e)),
+    };
+    handle
+        .upgrade_in_event_loop(move |h| {
+            let data = h.global::<BulkUpgradeData>();
+            data.set_busy(false);
+            data.set_can_rollback(!success);
+            data.set_message(message.into());
+        })
+        .unwrap();
+    success
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_reports_the_files_it_could_not_write() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        let manifest = directory.join("Cargo.toml");
+        std::fs::write(&manifest, "upgraded").unwrap();
+        let missing = directory.join("missing").join("Cargo.toml");
+        let backup: Backup = vec![
+            (directory.join("Cargo.lock"), None),
+            (missing.clone(), Some("original".into())),
+            (manifest.clone(), Some("original".into())),
+        ];
+
+        let error = format!("{:#}", restore(&backup).unwrap_err());
+        assert!(error.contains(&missing.display().to_string()), "{}", error);
+        assert!(!error.contains("Cargo.lock"), "{}", error);
+        // The files after the failing one are restored too
+        assert_eq!(std::fs::read_to_string(&manifest).unwrap(), "original");
+    }
+}
@@ -2,11 +2,34 @@
  * SPDX-License-Identifier: MIT OR Apache-2.0
  */
 
-use super::{Action, CargoUI, CratesCompletionData, DependencyData, DependencyNode, Diag, Feature};
+use super::{
+    Action, BenchData, BuildProgress, BulkUpgradeData, CargoProfile, CargoUI, CratesCompletionData,
+    DependencyData, DependencyNode, Diag, EnvVar, Feature, RunConfiguration, RunConfigurationData,
+    ToolchainOverride, UpgradeCandidate,
+};
+use crate::bench::{self, BenchOutputParser};
+use crate::bulk_upgrade;
+use crate::console;
+use crate::dependency_features;
+use crate::dependency_versions;
+use crate::diagnostics::DiagnosticsModel;
+use crate::editor;
+use crate::environment::{self, parse_env};
+use crate::fixes::{FixStore, Fixable};
+use crate::history;
+use crate::manifest_edits::{self, ManifestEdits};
+use crate::process;
+use crate::profiles;
+use crate::settings::WorkspaceSettings;
+use crate::targets::read_targets;
+use crate::test_runner::{self, list_tests, TestOutputParser, TestRunner};
+use crate::toolchain_file::{read_toolchain_file, save_toolchain_file};
+use crate::watch;
 use anyhow::Context;
 use cargo_metadata::{
-    diagnostic::DiagnosticLevel, semver::Version, DependencyKind, Metadata, Node, PackageId,
-    TargetKind,
+    diagnostic::{DiagnosticLevel, DiagnosticSpan},
+    semver::VersionReq,
+    DependencyKind, Metadata, Node, PackageId, TargetKind,
 };
 use futures::future::{Fuse, FusedFuture, FutureExt};
 use itertools::Itertools;
@@ -16,11 +39,10 @@ use std::cell::RefCell;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::str::FromStr;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FeatureSettings {
     enabled_features: Vec<SharedString>,
     enable_default_features: bool,
@@ -32,9 +54,42 @@ pub enum CargoMessage {
     Action {
         action: Action,
         feature_settings: FeatureSettings,
+        /// The command of a run of the history, started as it was instead of from the action
+        invocation: Option<history::Invocation>,
     },
     ReloadManifest(SharedString),
     PackageSelected(SharedString),
+    ToolchainSelected(SharedString),
+    /// Apply the compiler suggestion of the diagnostic with the given id
+    ApplyFix(i32),
+    /// Apply all the machine applicable suggestions of the last build
+    ApplyAllFixes,
+    /// Open a file at a given line and column in the user's editor
+    OpenInEditor {
+        file: SharedString,
+        line: i32,
+        column: i32,
+    },
+    /// A line typed by the user for the standard input of the running program
+    ConsoleInput(SharedString),
+    /// Close the standard input of the running program
+    CloseConsoleInput,
+    /// Build the tests of the current package and list them in the Tests tab
+    ListTests {
+        feature_settings: FeatureSettings,
+        runner: TestRunner,
+    },
+    /// Write the settings of a profile in the workspace's manifest
+    SaveProfile(CargoProfile),
+    /// Run the last action again when the sources change
+    SetWatch(bool),
+    /// Add or replace a run configuration of the workspace
+    SaveRunConfiguration(RunConfiguration),
+    RemoveRunConfiguration(SharedString),
+    /// The test runner was changed in the Tests tab, to be remembered for the workspace
+    TestRunnerChanged(TestRunner),
+    /// Create or update the rust-toolchain.toml of the workspace
+    SaveToolchainFile(ToolchainOverride),
     ShowOpenDialog,
     Cancel,
     /// Remove the dependency `.1` from package `.0`
@@ -43,15 +98,55 @@ pub enum CargoMessage {
         crate_name: SharedString,
         dep_kind: DependencyKind,
     },
-    /// Upgrade the dependency `.1` in package `.0`
+    /// Change the requirement of the dependency `.1` in package `.0`
     DependencyUpgrade {
         parent_package: SharedString,
         crate_name: SharedString,
         dep_kind: DependencyKind,
+        version: SharedString,
     },
     DependencyAdd {
         crate_name: SharedString,
         dep_kind: DependencyKind,
+        version: SharedString,
+    },
+    /// Update the dependency `crate_name` from the locked `version` to `precise` in the lockfile
+    DependencyUpdateLockfile {
+        crate_name: SharedString,
+        version: SharedString,
+        precise: SharedString,
+    },
+    /// Move the dependency to `[workspace.dependencies]` and make the members inherit it
+    DependencyPromote(SharedString),
+    /// List the outdated dependencies of the workspace in the upgrade dialog
+    BulkUpgradeList,
+    /// Upgrade the given dependencies, then check that the workspace still builds
+    BulkUpgrade {
+        candidates: Vec<UpgradeCandidate>,
+        allow_breaking: bool,
+    },
+    /// Restore the manifests and the lockfile from before the last upgrade
+    BulkUpgradeRollback,
+    /// Open the version picker to add the dependency `.1`, or to upgrade it in package `.0` if
+    /// not empty
+    DependencyVersions {
+        parent_package: SharedString,
+        crate_name: SharedString,
+        dep_kind: DependencyKind,
+    },
+    /// Open the feature editor of the dependency `.1` of package `.0`
+    DependencyFeatures {
+        parent_package: SharedString,
+        crate_name: SharedString,
+        dep_kind: DependencyKind,
+    },
+    /// Write the features of the dependency `.1` in the manifest of package `.0`
+    DependencySetFeatures {
+        parent_package: SharedString,
+        crate_name: SharedString,
+        dep_kind: DependencyKind,
+        features: Vec<SharedString>,
+        default_features: bool,
     },
     Install(InstallJob),
     UpdateCompletion(SharedString),
@@ -94,16 +189,35 @@ async fn cargo_worker_loop(
     let mut metadata: Option<Metadata> = None;
     let mut crates_index: Option<crates_index::GitIndex> = None;
     let mut package = SharedString::default();
+    let mut toolchain = SharedString::default();
+    let mut settings: Option<WorkspaceSettings> = None;
+    let fixes = Rc::new(RefCell::new(FixStore::default()));
     let mut update_features = true;
     let mut install_queue = VecDeque::new();
     let mut currently_installing = SharedString::default();
+    let mut run_control: Option<UnboundedSender<RunControl>> = None;
+    let mut watch = false;
+    let mut last_action: Option<RunRequest> = None;
+    // started once the run in progress is cancelled
+    let mut next_action: Option<RunRequest> = None;
+    let mut upgrade_backup: Option<bulk_upgrade::Backup> = None;
 
     let run_cargo_future = Fuse::terminated();
-    let read_metadata_future = read_metadata(manifest.clone(), handle.clone()).fuse();
+    let read_metadata_future =
+        read_metadata(manifest.clone(), toolchain.clone(), handle.clone()).fuse();
     let load_crate_index_future = load_crate_index().fuse();
     let install_completion_future = Fuse::terminated();
     let refresh_install_list_future = refresh_install_list(handle.clone()).fuse();
     let process_install_future = Fuse::terminated();
+    let toolchain_file_future = Fuse::terminated();
+    let list_tests_future = Fuse::terminated();
+    let read_targets_future = Fuse::terminated();
+    let watch_future = Fuse::terminated();
+    let update_lockfile_future = Fuse::terminated();
+    let bulk_check_future = Fuse::terminated();
+    let manifest_watch_future = Fuse::terminated();
+    // the manifests of the last workspace that could be read, to reload it once it is fixed
+    let mut manifest_paths = Vec::new();
     futures::pin_mut!(
         run_cargo_future,
         read_metadata_future,
@@ -111,18 +225,91 @@ async fn cargo_worker_loop(
         refresh_install_list_future,
         process_install_future,
         install_completion_future,
+        toolchain_file_future,
+        list_tests_future,
+        read_targets_future,
+        watch_future,
+        update_lockfile_future,
+        bulk_check_future,
+        manifest_watch_future,
     );
     loop {
         let m = futures::select! {
             res = run_cargo_future => {
-                res?;
-                continue;
+                let outcome: RunOutcome = res?;
+                if let Some(metadata) = &metadata {
+                    if !outcome.measurements.is_empty() {
+                        bench::record(
+                            outcome.measurements,
+                            metadata.workspace_root.as_std_path(),
+                            metadata.target_directory.clone().into_std_path_buf(),
+                            handle.clone(),
+                        )
+                        .await;
+                    }
+                    if let Some(run) = outcome.run {
+                        match history::record(&run, metadata.target_directory.as_std_path()) {
+                            Ok(path) => history::prepend(&run, &path, handle.clone()),
+                            Err(e) => handle
+                                .clone()
+                                .upgrade_in_event_loop(move |h| h.set_status(format!("{:#}", e).into()))
+                                .unwrap(),
+                        }
+                    }
+                }
+                match next_action.take() {
+                    Some(request) => request.into_message(),
+                    None => continue,
+                }
             }
             res = read_metadata_future => {
                 metadata = res;
                 if let Some(metadata) = &metadata {
+                    manifest_paths = watch::manifest_paths(metadata);
+                }
+                // Reload when the manifests are edited outside of cargo-ui, or `cargo update` is run
+                let paths = if manifest_paths.is_empty() {
+                    vec![manifest.path_to_cargo_toml().to_owned()]
+                } else {
+                    manifest_paths.clone()
+                };
+                manifest_watch_future.set(watch::wait_for_change(paths, None).fuse());
+                if let Some(metadata) = &metadata {
+                    settings = load_settings(metadata, handle.clone());
+                    match profiles::load(metadata.workspace_root.as_std_path()) {
+                        Ok(p) => profiles::apply(p, handle.clone()),
+                        Err(e) => handle
+                            .clone()
+                            .upgrade_in_event_loop(move |h| h.set_status(format!("{:#}", e).into()))
+                            .unwrap(),
+                    }
+                    let workspace_toolchain = settings.as_ref().map(|s| s.toolchain()).unwrap_or_default();
+                    if workspace_toolchain != toolchain {
+                        // The metadata needs to be read again with the toolchain of this workspace
+                        toolchain = workspace_toolchain;
+                        let tc = toolchain.clone();
+                        handle.clone().upgrade_in_event_loop(move |h| h.set_current_toolchain(tc)).unwrap();
+                        read_metadata_future.set(
+                            read_metadata(manifest.clone(), toolchain.clone(), handle.clone()).fuse(),
+                        );
+                        continue;
+                    }
                     apply_metadata(metadata, crates_index.as_ref(), update_features, &mut package, handle.clone());
                     update_features = false;
+                    history::load(metadata.target_directory.as_std_path(), handle.clone());
+                    if watch {
+                        watch_future.set(watch::wait_for_source_change(metadata, &package).fuse());
+                    }
+                    if let Some(directory) = manifest.directory() {
+                        toolchain_file_future.set(read_toolchain_file(
+                            directory.to_owned(),
+                            metadata.workspace_root.clone().into_std_path_buf(),
+                            handle.clone(),
+                        ).fuse());
+                        read_targets_future.set(
+                            read_targets(directory.to_owned(), toolchain.clone(), handle.clone()).fuse(),
+                        );
+                    }
                 }
                 continue;
             }
@@ -150,13 +337,46 @@ async fn cargo_worker_loop(
                 refresh_install_list_future.set(refresh_install_list(handle.clone()).fuse());
                 if let Some(job) = install_queue.pop_front() {
                     currently_installing = job.crate_name().clone();
-                    process_install_future.set(process_install(job, handle.clone()).fuse());
+                    process_install_future
+                        .set(process_install(job, toolchain.clone(), handle.clone()).fuse());
                 } else {
                     currently_installing = Default::default();
                 }
                 continue;
             }
             _ = install_completion_future => { continue; }
+            _ = toolchain_file_future => { continue; }
+            _ = list_tests_future => { continue; }
+            _ = read_targets_future => { continue; }
+            success = bulk_check_future => {
+                // Only a failed upgrade can be rolled back
+                if success {
+                    upgrade_backup = None;
+                }
+                read_metadata_future
+                    .set(read_metadata(manifest.clone(), toolchain.clone(), handle.clone()).fuse());
+                continue;
+            }
+            _ = update_lockfile_future => {
+                read_metadata_future
+                    .set(read_metadata(manifest.clone(), toolchain.clone(), handle.clone()).fuse());
+                continue;
+            }
+            _ = manifest_watch_future => {
+                read_metadata_future
+                    .set(read_metadata(manifest.clone(), toolchain.clone(), handle.clone()).fuse());
+                continue;
+            }
+            _ = watch_future => {
+                match (&last_action, &metadata) {
+                    (Some(request), Some(metadata)) if watch => {
+                        watch_future.set(watch::wait_for_source_change(metadata, &package).fuse());
+                        // Replaces the run in progress, if any
+                        request.clone().into_message()
+                    }
+                    _ => continue,
+                }
+            }
             m = r.recv().fuse() => {
                 match m {
                     None => return Ok(()),
@@ -170,20 +390,135 @@ async fn cargo_worker_loop(
             CargoMessage::Action {
                 action,
                 feature_settings,
-            } => run_cargo_future
-                .set(run_cargo(action, feature_settings, manifest.clone(), handle.clone()).fuse()),
+                invocation,
+            } => {
+                let request = RunRequest {
+                    action,
+                    feature_settings,
+                    invocation,
+                };
+                // `cargo fix` would trigger itself
+                if request.action.command != "fix" {
+                    last_action = Some(request.clone());
+                }
+                // The run in progress is interrupted as if it was cancelled, so that it can stop
+                // cleanly and be recorded in the history, then this one starts
+                if !run_cargo_future.is_terminated() {
+                    let cancelling = next_action.is_some()
+                        || run_control
+                            .as_ref()
+                            .is_some_and(|c| c.send(RunControl::Cancel).is_ok());
+                    if cancelling {
+                        next_action = Some(request);
+                        continue;
+                    }
+                }
+                let total_units = metadata
+                    .as_ref()
+                    .map_or(0, |metadata| count_build_units(metadata, &request.action));
+                let (control_sender, control_receiver) = tokio::sync::mpsc::unbounded_channel();
+                run_control = Some(control_sender);
+                run_cargo_future.set(
+                    run_cargo(
+                        request,
+                        total_units,
+                        fixes.clone(),
+                        control_receiver,
+                        manifest.clone(),
+                        handle.clone(),
+                    )
+                    .fuse(),
+                )
+            }
             CargoMessage::Cancel => {
-                run_cargo_future.set(Fuse::terminated());
+                next_action = None;
+                let asked = !run_cargo_future.is_terminated()
+                    && run_control
+                        .as_ref()
+                        .is_some_and(|c| c.send(RunControl::Cancel).is_ok());
+                if !asked {
+                    run_cargo_future.set(Fuse::terminated());
+                }
+            }
+            CargoMessage::SetWatch(enabled) => {
+                watch = enabled;
+                match &metadata {
+                    Some(metadata) if watch => {
+                        watch_future.set(watch::wait_for_source_change(metadata, &package).fuse())
+                    }
+                    _ => watch_future.set(Fuse::terminated()),
+                }
+            }
+            CargoMessage::ConsoleInput(text) => {
+                if let Some(run_control) = &run_control {
+                    let _ = run_control.send(RunControl::Input(text.into()));
+                }
+            }
+            CargoMessage::CloseConsoleInput => {
+                if let Some(run_control) = &run_control {
+                    let _ = run_control.send(RunControl::CloseInput);
+                }
+            }
+            CargoMessage::ListTests {
+                feature_settings,
+                runner,
+            } => {
+                list_tests_future.set(
+                    list_tests(
+                        manifest.path_to_cargo_toml().to_owned(),
+                        package.clone(),
+                        toolchain.clone(),
+                        feature_settings,
+                        runner,
+                        handle.clone(),
+                    )
+                    .fuse(),
+                );
+            }
+            CargoMessage::SaveProfile(profile) => {
+                if let Some(metadata) = &metadata {
+                    let root = metadata.workspace_root.as_std_path();
+                    match profiles::save(root, &profile).and_then(|()| profiles::load(root)) {
+                        Ok(p) => profiles::apply(p, handle.clone()),
+                        Err(e) => handle
+                            .clone()
+                            .upgrade_in_event_loop(move |h| {
+                                h.set_status(format!("{:#}", e).into());
+                            })
+                            .unwrap(),
+                    }
+                }
+            }
+            CargoMessage::SaveRunConfiguration(configuration) => {
+                if let Some(settings) = &mut settings {
+                    settings.set_run_configuration(&configuration);
+                    save_settings(settings, handle.clone());
+                }
+            }
+            CargoMessage::RemoveRunConfiguration(name) => {
+                if let Some(settings) = &mut settings {
+                    settings.remove_run_configuration(&name);
+                    save_settings(settings, handle.clone());
+                }
+            }
+            CargoMessage::TestRunnerChanged(runner) => {
+                if let Some(settings) = &mut settings {
+                    settings.set_test_runner(test_runner::runner_setting(&runner));
+                    settings.set_nextest_profile(&runner.profile);
+                    save_settings(settings, handle.clone());
+                }
             }
             CargoMessage::ReloadManifest(m) => {
                 manifest = PathBuf::from(m.as_str()).into();
                 update_features = true;
-                read_metadata_future.set(read_metadata(manifest.clone(), handle.clone()).fuse());
+                read_metadata_future
+                    .set(read_metadata(manifest.clone(), toolchain.clone(), handle.clone()).fuse());
             }
             CargoMessage::ShowOpenDialog => {
                 manifest = show_open_dialog(manifest);
                 update_features = true;
-                read_metadata_future.set(read_metadata(manifest.clone(), handle.clone()).fuse());
+                read_metadata_future
+                    .set(read_metadata(manifest.clone(), toolchain.clone(), handle.clone()).fuse());
             }
             CargoMessage::PackageSelected(pkg) => {
                 package = pkg;
@@ -195,6 +530,85 @@ async fn cargo_worker_loop(
                         &mut package,
                         handle.clone(),
                     );
+                    if watch {
+                        watch_future.set(watch::wait_for_source_change(metadata, &package).fuse());
+                    }
+                }
+            }
+            CargoMessage::ToolchainSelected(tc) => {
+                toolchain = tc;
+                if let Some(settings) = &mut settings {
+                    settings.set_toolchain(&toolchain);
+                    if let Err(e) = settings.save() {
+                        handle
+                            .clone()
+                            .upgrade_in_event_loop(move |h| {
+                                h.set_status(format!("{:#}", e).into());
+                            })
+                            .unwrap();
+                    }
+                }
+                read_metadata_future
+                    .set(read_metadata(manifest.clone(), toolchain.clone(), handle.clone()).fuse());
+            }
+            CargoMessage::ApplyFix(id) => {
+                if let Some(metadata) = &metadata {
+                    apply_fixes(
+                        &mut fixes.borrow_mut(),
+                        &[id],
+                        metadata.workspace_root.as_std_path(),
+                        handle.clone(),
+                    );
+                }
+            }
+            CargoMessage::ApplyAllFixes => {
+                if let Some(metadata) = &metadata {
+                    let ids = fixes.borrow().machine_applicable();
+                    apply_fixes(
+                        &mut fixes.borrow_mut(),
+                        &ids,
+                        metadata.workspace_root.as_std_path(),
+                        handle.clone(),
+                    );
+                }
+            }
+            CargoMessage::OpenInEditor { file, line, column } => {
+                // The paths of rustc are relative to the workspace root
+                let path = match &metadata {
+                    Some(metadata) => metadata.workspace_root.as_std_path().join(file.as_str()),
+                    None => PathBuf::from(file.as_str()),
+                };
+                let template = settings.as_ref().and_then(|s| s.editor());
+                if let Err(e) = editor::open_in_editor(&path, line, column, template.as_deref()) {
+                    handle
+                        .clone()
+                        .upgrade_in_event_loop(move |h| {
+                            h.set_status(format!("{:#}", e).into());
+                        })
+                        .unwrap();
+                }
+            }
+            CargoMessage::SaveToolchainFile(toolchain_override) => {
+                if let Some((directory, metadata)) = manifest.directory().zip(metadata.as_ref()) {
+                    match save_toolchain_file(
+                        directory,
+                        metadata.workspace_root.as_std_path(),
+                        &toolchain_override,
+                    ) {
+                        // The toolchain used by cargo may have changed
+                        Ok(()) => read_metadata_future.set(
+                            read_metadata(manifest.clone(), toolchain.clone(), handle.clone())
+                                .fuse(),
+                        ),
+                        Err(e) => {
+                            handle
+                                .clone()
+                                .upgrade_in_event_loop(move |h| {
+                                    h.set_status(format!("{:#}", e).into());
+                                })
+                                .unwrap();
+                        }
+                    }
                 }
             }
             CargoMessage::DependencyRemove {
@@ -202,26 +616,17 @@ async fn cargo_worker_loop(
                 crate_name,
                 dep_kind,
             } => {
-                let pkg = PackageId {
-                    repr: parent_package.into(),
-                };
-                if let Some(pkg) = metadata
-                    .as_ref()
-                    .and_then(|metadata| metadata.packages.iter().find(|p| p.id == pkg))
-                {
-                    match dependency_remove(
-                        pkg.manifest_path.as_ref(),
-                        crate_name.as_str(),
-                        dep_kind,
-                    ) {
-                        Ok(()) => read_metadata_future
-                            .set(read_metadata(manifest.clone(), handle.clone()).fuse()),
+                if let Some(metadata) = metadata.as_ref() {
+                    match dependency_remove(metadata, &parent_package, &crate_name, dep_kind) {
+                        Ok(()) => read_metadata_future.set(
+                            read_metadata(manifest.clone(), toolchain.clone(), handle.clone())
+                                .fuse(),
+                        ),
                         Err(e) => {
-                            dbg!(e);
                             handle
                                 .clone()
-                                .upgrade_in_event_loop(|h| {
-                                    h.set_status("Not yet supported".into());
+                                .upgrade_in_event_loop(move |h| {
+                                    h.set_status(format!("{:#}", e).into());
                                 })
                                 .unwrap();
                         }
@@ -231,34 +636,31 @@ async fn cargo_worker_loop(
             CargoMessage::DependencyAdd {
                 crate_name,
                 dep_kind,
+                version,
             } => {
-                if let Some((pkg, cr)) = metadata
-                    .as_ref()
-                    .and_then(|metadata| {
-                        let pkg = package.as_str();
-                        if pkg.is_empty() {
-                            Some(&metadata[metadata.workspace_members.first()?])
-                        } else {
-                            metadata.packages.iter().find(|p| p.name == pkg)
-                        }
-                    })
-                    .and_then(|p| Some((p, crates_index.as_ref()?.crate_(&crate_name)?)))
-                {
+                if let Some(pkg) = metadata.as_ref().and_then(|metadata| {
+                    let pkg = package.as_str();
+                    if pkg.is_empty() {
+                        Some(&metadata[metadata.workspace_members.first()?])
+                    } else {
+                        metadata.packages.iter().find(|p| p.name == pkg)
+                    }
+                }) {
                     match dependency_add(
                         pkg.manifest_path.as_ref(),
                         crate_name.as_str(),
-                        cr.highest_normal_version()
-                            .unwrap_or(cr.highest_version())
-                            .version(),
+                        version.as_str(),
                         dep_kind,
                     ) {
-                        Ok(()) => read_metadata_future
-                            .set(read_metadata(manifest.clone(), handle.clone()).fuse()),
+                        Ok(()) => read_metadata_future.set(
+                            read_metadata(manifest.clone(), toolchain.clone(), handle.clone())
+                                .fuse(),
+                        ),
                         Err(e) => {
                             handle
                                 .clone()
                                 .upgrade_in_event_loop(move |h| {
-                                    h.set_status(format!("{}", e).into());
+                                    h.set_status(format!("{:#}", e).into());
                                 })
                                 .unwrap();
                         }
@@ -269,41 +671,208 @@ async fn cargo_worker_loop(
                 parent_package,
                 crate_name,
                 dep_kind,
+                version,
             } => {
-                let pkg = PackageId {
-                    repr: parent_package.into(),
-                };
-                if let Some((pkg, cr)) = metadata
-                    .as_ref()
-                    .and_then(|metadata| metadata.packages.iter().find(|p| p.id == pkg))
-                    .and_then(|p| Some((p, crates_index.as_ref()?.crate_(&crate_name)?)))
-                {
+                if let Some(metadata) = metadata.as_ref() {
                     match dependency_upgrade_to_version(
-                        pkg.manifest_path.as_ref(),
-                        crate_name.as_str(),
-                        cr.highest_normal_version()
-                            .unwrap_or(cr.highest_version())
-                            .version(),
+                        metadata,
+                        &parent_package,
+                        &crate_name,
+                        &version,
                         dep_kind,
                     ) {
-                        Ok(()) => read_metadata_future
-                            .set(read_metadata(manifest.clone(), handle.clone()).fuse()),
+                        Ok(()) => read_metadata_future.set(
+                            read_metadata(manifest.clone(), toolchain.clone(), handle.clone())
+                                .fuse(),
+                        ),
                         Err(e) => {
-                            dbg!(e);
                             handle
                                 .clone()
-                                .upgrade_in_event_loop(|h| {
-                                    h.set_status("Not yet supported".into());
+                                .upgrade_in_event_loop(move |h| {
+                                    h.set_status(format!("{:#}", e).into());
                                 })
                                 .unwrap();
                         }
                     }
                 }
             }
+            CargoMessage::DependencyUpdateLockfile {
+                crate_name,
+                version,
+                precise,
+            } => {
+                update_lockfile_future.set(
+                    update_lockfile(
+                        manifest.clone(),
+                        toolchain.clone(),
+                        format!("{}@{}", crate_name, version),
+                        precise,
+                        handle.clone(),
+                    )
+                    .fuse(),
+                );
+            }
+            CargoMessage::DependencyPromote(crate_name) => {
+                if let Some(metadata) = &metadata {
+                    match manifest_edits::promote_to_workspace(metadata, &crate_name) {
+                        Ok(()) => read_metadata_future.set(
+                            read_metadata(manifest.clone(), toolchain.clone(), handle.clone())
+                                .fuse(),
+                        ),
+                        Err(e) => handle
+                            .clone()
+                            .upgrade_in_event_loop(move |h| {
+                                h.set_status(format!("{:#}", e).into());
+                            })
+                            .unwrap(),
+                    }
+                }
+            }
+            CargoMessage::BulkUpgradeList => match (&metadata, &crates_index) {
+                (Some(metadata), Some(index)) => {
+                    bulk_upgrade::list(metadata, index, handle.clone())
+                }
+                (_, None) => handle
+                    .clone()
+                    .upgrade_in_event_loop(|h| {
+                        h.set_status("The crates index is not loaded yet".into());
+                    })
+                    .unwrap(),
+                (None, _) => {}
+            },
+            CargoMessage::BulkUpgrade {
+                candidates,
+                allow_breaking,
+            } => {
+                if let Some(metadata) = &metadata {
+                    match bulk_upgrade::apply(metadata, &candidates, allow_breaking) {
+                        Ok(backup) => {
+                            upgrade_backup = Some(backup);
+                            bulk_check_future.set(
+                                bulk_upgrade::check(
+                                    manifest.path_to_cargo_toml().to_owned(),
+                                    toolchain.clone(),
+                                    handle.clone(),
+                                )
+                                .fuse(),
+                            );
+                        }
+                        Err(e) => handle
+                            .clone()
+                            .upgrade_in_event_loop(move |h| {
+                                h.global::<BulkUpgradeData>()
+                                    .set_message(format!("{:#}", e).into());
+                            })
+                            .unwrap(),
+                    }
+                }
+            }
+            CargoMessage::BulkUpgradeRollback => {
+                if let Some(backup) = upgrade_backup.take() {
+                    let message = match bulk_upgrade::restore(&backup) {
+                        Ok(()) => "The manifests and the lockfile were restored".to_owned(),
+                        Err(e) => {
+                            // Rolling back again may work once the files can be written
+                            upgrade_backup = Some(backup);
+                            format!("{:#}", e)
+                        }
+                    };
+                    let can_rollback = upgrade_backup.is_some();
+                    handle
+                        .clone()
+                        .upgrade_in_event_loop(move |h| {
+                            let data = h.global::<BulkUpgradeData>();
+                            data.set_can_rollback(can_rollback);
+                            data.set_message(message.into());
+                        })
+                        .unwrap();
+                    read_metadata_future.set(
+                        read_metadata(manifest.clone(), toolchain.clone(), handle.clone()).fuse(),
+                    );
+                }
+            }
+            CargoMessage::DependencyVersions {
+                parent_package,
+                crate_name,
+                dep_kind,
+            } => {
+                let result = match (&metadata, &crates_index) {
+                    (Some(metadata), Some(index)) => dependency_versions::show(
+                        metadata,
+                        index,
+                        parent_package,
+                        crate_name,
+                        dep_kind,
+                        handle.clone(),
+                    ),
+                    (_, None) => Err(anyhow::anyhow!("The crates index is not loaded yet")),
+                    (None, _) => Ok(()),
+                };
+                if let Err(e) = result {
+                    handle
+                        .clone()
+                        .upgrade_in_event_loop(move |h| {
+                            h.set_status(format!("{:#}", e).into());
+                        })
+                        .unwrap();
+                }
+            }
+            CargoMessage::DependencyFeatures {
+                parent_package,
+                crate_name,
+                dep_kind,
+            } => {
+                if let Some(metadata) = &metadata {
+                    if let Err(e) = dependency_features::show(
+                        metadata,
+                        parent_package,
+                        crate_name,
+                        dep_kind,
+                        handle.clone(),
+                    ) {
+                        handle
+                            .clone()
+                            .upgrade_in_event_loop(move |h| {
+                                h.set_status(format!("{:#}", e).into());
+                            })
+                            .unwrap();
+                    }
+                }
+            }
+            CargoMessage::DependencySetFeatures {
+                parent_package,
+                crate_name,
+                dep_kind,
+                features,
+                default_features,
+            } => {
+                if let Some(metadata) = &metadata {
+                    match dependency_features::save(
+                        metadata,
+                        &parent_package,
+                        &crate_name,
+                        dep_kind,
+                        &features,
+                        default_features,
+                    ) {
+                        Ok(()) => read_metadata_future.set(
+                            read_metadata(manifest.clone(), toolchain.clone(), handle.clone())
+                                .fuse(),
+                        ),
+                        Err(e) => handle
+                            .clone()
+                            .upgrade_in_event_loop(move |h| {
+                                h.set_status(format!("{:#}", e).into());
+                            })
+                            .unwrap(),
+                    }
+                }
+            }
             CargoMessage::Install(job) => {
                 if process_install_future.is_terminated() {
                     currently_installing = job.crate_name().clone();
-                    process_install_future.set(process_install(job, handle.clone()).fuse());
+                    process_install_future
+                        .set(process_install(job, toolchain.clone(), handle.clone()).fuse());
                 } else {
                     install_queue.push_back(job);
                 }
@@ -329,42 +898,74 @@ async fn load_crate_index() -> Result<crates_index::GitIndex, String> {
     .map_err(|x| x.to_string())?
 }
 
-async fn run_cargo(
+/// Sent to a run of cargo while it is in progress
+enum RunControl {
+    /// A line for the standard input of the program
+    Input(String),
+    CloseInput,
+    /// Interrupt cargo, or kill it if it was already interrupted
+    Cancel,
+}
+
+/// How long cargo has to stop after being interrupted, before it is killed
+const CANCEL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// What `CargoMessage::Action` asks to run
+#[derive(Debug, Clone)]
+struct RunRequest {
     action: Action,
-    features: FeatureSettings,
-    manifest: Manifest,
-    handle: slint::Weak<CargoUI>,
-) -> tokio::io::Result<()> {
-    handle
-        .clone()
-        .upgrade_in_event_loop(|h| {
-            h.set_status("".into());
-            h.set_is_building(true);
-            let diagnostics_model = Rc::new(VecModel::<Diag>::default());
-            h.set_diagnostics(ModelRc::from(
-                diagnostics_model.clone() as Rc<dyn Model<Data = Diag>>
-            ));
-        })
-        .unwrap();
+    feature_settings: FeatureSettings,
+    invocation: Option<history::Invocation>,
+}
 
-    struct ResetIsBuilding(slint::Weak<CargoUI>);
-    impl Drop for ResetIsBuilding {
-        fn drop(&mut self) {
-            self.0
-                .clone()
-                .upgrade_in_event_loop(move |h| h.set_is_building(false))
-                .unwrap()
+impl RunRequest {
+    fn into_message(self) -> CargoMessage {
+        CargoMessage::Action {
+            action: self.action,
+            feature_settings: self.feature_settings,
+            invocation: self.invocation,
         }
     }
-    let _reset_is_building = ResetIsBuilding(handle.clone());
+}
+
+/// What is left of a run of cargo once it finished
+struct RunOutcome {
+    measurements: Vec<bench::Measurement>,
+    /// None if cargo was not run at all
+    run: Option<history::Run>,
+}
 
-    let mut cargo_command = cargo_command();
+/// The cargo command of an action, or the error to show if its arguments cannot be parsed
+fn action_command(
+    action: &Action,
+    features: &FeatureSettings,
+    manifest: &Manifest,
+) -> Result<tokio::process::Command, &'static str> {
+    let mut cargo_command = cargo_command(&action.toolchain);
     cargo_command.arg(action.command.as_str());
+    if action.command == "nextest" {
+        cargo_command.arg("run");
+    }
     cargo_command
         .arg("--manifest-path")
         .arg(manifest.path_to_cargo_toml());
-    if action.profile == "release" {
-        cargo_command.arg("--release");
+    // Run from the workspace so that rustup picks up its rust-toolchain.toml
+    if let Some(directory) = manifest.directory() {
+        cargo_command.current_dir(directory);
+        if !action.working_directory.is_empty() {
+            // relative to the workspace
+            cargo_command.current_dir(directory.join(action.working_directory.as_str()));
+        }
+    }
+    cargo_command.envs(parse_env(&action.env));
+    cargo_command.args(profiles::to_args(&action.profile));
+    if !action.target_triple.is_empty() {
+        cargo_command
+            .arg("--target")
+            .arg(action.target_triple.as_str());
+    }
+    if action.command == "fix" && action.extra == "allow-dirty" {
+        cargo_command.args(["--allow-dirty", "--allow-staged"]);
     }
     if action.command == "run" && !action.extra.is_empty() {
         if let Some(example) = action.extra.strip_suffix(" (example)") {
@@ -373,56 +974,320 @@ async fn run_cargo(
             cargo_command.arg("--bin").arg(action.extra.as_str());
         }
     } else if action.command == "test" && !action.extra.is_empty() {
-        cargo_command.arg("--test").arg(action.extra.as_str());
+        cargo_command.args(test_runner::target_args(&action.extra));
+    } else if action.command == "bench" && !action.extra.is_empty() {
+        cargo_command.arg("--bench").arg(action.extra.as_str());
+    } else if action.command == "nextest" && !action.extra.is_empty() {
+        // the profile and filter expression
+        let options = shlex::split(&action.extra).ok_or("Error parsing the nextest filter")?;
+        cargo_command.args(options);
     }
     if !action.package.is_empty() {
         cargo_command.arg("-p").arg(action.package.as_str());
     }
     features.to_args(&mut cargo_command);
-    cargo_command.args(["--message-format", "json"]);
+    if action.command == "nextest" {
+        // The results of the tests are printed in the json format of libtest
+        cargo_command.args(["--cargo-message-format", "json"]);
+        cargo_command.args(["--message-format", "libtest-json"]);
+        cargo_command.env("NEXTEST_EXPERIMENTAL_LIBTEST_JSON", "1");
+    } else {
+        cargo_command.args(["--message-format", "json"]);
+    }
 
     if !action.arguments.is_empty() {
         cargo_command.arg("--");
-        if let Some(args) = shlex::split(&action.arguments) {
-            cargo_command.args(args);
-        } else {
+        let arguments =
+            shlex::split(&action.arguments).ok_or("Error parsing command line arguments")?;
+        cargo_command.args(arguments);
+    }
+    Ok(cargo_command)
+}
+
+async fn run_cargo(
+    request: RunRequest,
+    total_units: i32,
+    fixes: Rc<RefCell<FixStore>>,
+    mut control: UnboundedReceiver<RunControl>,
+    manifest: Manifest,
+    handle: slint::Weak<CargoUI>,
+) -> tokio::io::Result<RunOutcome> {
+    let RunRequest {
+        action,
+        feature_settings: features,
+        invocation,
+    } = request;
+    fixes.borrow_mut().clear();
+    let mut configuration = RunConfiguration {
+        action: action.clone(),
+        ..Default::default()
+    };
+    features.store(&mut configuration);
+    // The output of the programs that are run goes to a console, separate from the diagnostics
+    let has_console = matches!(
+        action.command.as_str(),
+        "run" | "test" | "bench" | "nextest"
+    );
+    let is_test = matches!(action.command.as_str(), "test" | "nextest");
+    let is_bench = action.command == "bench";
+    handle
+        .clone()
+        .upgrade_in_event_loop(move |h| {
+            h.set_status("".into());
+            h.set_fix_needs_allow_dirty(false);
+            h.set_is_building(true);
+            h.set_build_progress(BuildProgress {
+                total: total_units,
+                ..Default::default()
+            });
+            let diagnostics_model = Rc::new(DiagnosticsModel::new(h.get_diagnostics_filter()));
+            h.set_diagnostics(ModelRc::from(
+                diagnostics_model as Rc<dyn Model<Data = Diag>>,
+            ));
+            console::reset(&h, has_console);
+            if is_bench {
+                bench::reset(&h);
+            }
+        })
+        .unwrap();
+
+    /// Also called when the build is cancelled
+    struct ResetIsBuilding(slint::Weak<CargoUI>, bool);
+    impl Drop for ResetIsBuilding {
+        fn drop(&mut self) {
+            let is_test = self.1;
+            self.0
+                .clone()
+                .upgrade_in_event_loop(move |h| {
+                    h.set_is_building(false);
+                    h.set_console_input_open(false);
+                    if is_test {
+                        test_runner::finish_test_run(&h);
+                    }
+                })
+                .unwrap()
+        }
+    }
+    let _reset_is_building = ResetIsBuilding(handle.clone(), is_test);
+
+    let cargo_command = match &invocation {
+        Some(invocation) => Ok(invocation.command()),
+        None => action_command(&action, &features, &manifest),
+    };
+    let mut cargo_command = match cargo_command {
+        Ok(cargo_command) => cargo_command,
+        Err(error) => {
             handle
                 .clone()
                 .upgrade_in_event_loop(move |h| {
-                    h.set_status("Error parsing command line arguments".into());
+                    h.set_status(error.into());
                     h.set_build_pane_visible(false);
                 })
                 .unwrap();
-            return Ok(());
+            return Ok(RunOutcome {
+                measurements: Vec::new(),
+                run: None,
+            });
         }
+    };
+
+    if has_console {
+        cargo_command.stdin(std::process::Stdio::piped());
+        // The output is not a terminal, but it is shown with colors
+        cargo_command.env("CLICOLOR_FORCE", "1");
     }
 
+    let environment = environment::effective(cargo_command.as_std());
+    let command_line = history::command_line(cargo_command.as_std());
+    let invocation = history::Invocation::of(cargo_command.as_std());
+    let timestamp = history::now();
+    handle
+        .clone()
+        .upgrade_in_event_loop(move |h| {
+            h.set_last_run_environment(ModelRc::from(
+                Rc::new(VecModel::from(environment)) as Rc<dyn Model<Data = EnvVar>>
+            ));
+        })
+        .unwrap();
+
+    process::new_process_group(&mut cargo_command);
     let mut res = cargo_command
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .kill_on_drop(true)
         .spawn()?;
+    // When the run is replaced by another one, the children of cargo are killed too
+    let mut process_group = process::ProcessGroupGuard(res.id());
+    let mut control_open = true;
+    let mut cancelled = false;
+    let kill_deadline = tokio::time::sleep(std::time::Duration::ZERO);
+    tokio::pin!(kill_deadline);
 
     let mut stdout = BufReader::new(res.stdout.take().unwrap()).lines();
     let mut stderr = BufReader::new(res.stderr.take().unwrap()).lines();
+    // Written from a separate task, so that a program that does not read its input does not
+    // block its output nor the cancellation
+    let mut stdin = res.stdin.take().map(|mut writer| {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            while let Some(input) = receiver.recv().await {
+                if writer.write_all(input.as_bytes()).await.is_err() {
+                    // The program does not read its input anymore
+                    break;
+                }
+            }
+        });
+        sender
+    });
+    // Once cargo is done building, what comes next is the output of the program. The two streams
+    // are not in sync, so each one has its own marker: the `BuildFinished` message on stdout, and
+    // the `Finished` line on stderr.
+    let mut build_finished = false;
+    let mut stderr_build_finished = false;
+    let mut test_parser = is_test.then(TestOutputParser::default);
+    let mut bench_parser = is_bench.then(BenchOutputParser::default);
+    let mut measurements = Vec::new();
+    let mut diagnostics = Vec::new();
+    // The error of cargo itself, e.g. when `cargo fix` refuses to change uncommitted files
+    let mut cargo_error: Vec<String> = Vec::new();
+    let start_time = std::time::Instant::now();
+    let mut progress = BuildProgress {
+        total: total_units,
+        ..Default::default()
+    };
+    let mut elapsed_timer = tokio::time::interval(std::time::Duration::from_secs(1));
     loop {
         tokio::select! {
+            _ = elapsed_timer.tick() => {
+                progress.elapsed = format_duration(start_time.elapsed()).into();
+                let progress = progress.clone();
+                handle.clone().upgrade_in_event_loop(move |h| h.set_build_progress(progress)).unwrap();
+            }
+            message = control.recv(), if control_open => {
+                match message {
+                    Some(RunControl::Input(input)) => {
+                        let Some(sender) = stdin.as_ref() else { continue };
+                        if sender.send(format!("{}\n", input)).is_err() {
+                            stdin = None;
+                        }
+                        handle.clone().upgrade_in_event_loop(move |h| {
+                            console::push_line(&h, console::Stream::Stdin, console::parse_ansi(&input));
+                        }).unwrap();
+                    }
+                    Some(RunControl::CloseInput) => stdin = None,
+                    Some(RunControl::Cancel) => {
+                        let Some(pid) = process_group.0 else { continue };
+                        // The second time, the user does not want to wait anymore
+                        process::stop_process_group(pid, cancelled);
+                        if !cancelled {
+                            cancelled = true;
+                            kill_deadline.as_mut().reset(tokio::time::Instant::now() + CANCEL_TIMEOUT);
+                            handle.clone().upgrade_in_event_loop(|h| h.set_status("Cancelling...".into())).unwrap();
+                        }
+                    }
+                    None => control_open = false,
+                }
+            }
+            () = &mut kill_deadline, if cancelled => {
+                if let Some(pid) = process_group.0 {
+                    process::stop_process_group(pid, true);
+                }
+                // only once
+                kill_deadline.as_mut().reset(tokio::time::Instant::now() + std::time::Duration::from_secs(3600));
+            }
             line = stderr.next_line() => {
                 let line = if let Some(line) = line? { line } else { break };
+                let spans = console::parse_ansi(&line);
+                let line = console::strip_ansi(&line);
+                // Without a `Finished` line, e.g. with `--quiet`, the end of the build on stdout is
+                // the best guess
+                let program_output = stderr_build_finished || build_finished;
+                if program_output && has_console && !is_cargo_status(&line) {
+                    handle.clone().upgrade_in_event_loop(move |h| {
+                        console::push_line(&h, console::Stream::Stderr, spans);
+                    }).unwrap();
+                    continue;
+                }
+                if let Some(unit) = ["Compiling ", "Checking ", "Documenting "]
+                    .iter()
+                    .find_map(|prefix| line.trim_start().strip_prefix(prefix))
+                {
+                    progress.current = unit.into();
+                    let progress = progress.clone();
+                    handle.clone().upgrade_in_event_loop(move |h| h.set_build_progress(progress)).unwrap();
+                }
+                if line.trim_start().starts_with("Finished ") {
+                    stderr_build_finished = true;
+                }
+                if action.command == "fix" && (!cargo_error.is_empty() || line.starts_with("error: ")) {
+                    cargo_error.push(line.clone());
+                }
                 handle.clone().upgrade_in_event_loop(move |h| {
                     h.set_status(line.into());
                 }).unwrap();
             }
             line = stdout.next_line() => {
                 let line = if let Some(line) = line? { line } else { break };
+                if is_test && test_runner::is_json(&line) && !line.contains("\"reason\"") {
+                    // The results of the tests, even if cargo did not tell that the build finished
+                    build_finished = true;
+                }
+                if build_finished && has_console {
+                    let event = test_parser.as_mut().and_then(|parser| parser.parse_line(&line));
+                    let measurement = bench_parser.as_mut().and_then(|parser| parser.parse_line(&console::strip_ansi(&line)));
+                    measurements.extend(measurement.clone());
+                    // The json output of the tests is shown as the human readable one, nextest
+                    // already prints that on stderr
+                    let line = if test_runner::is_json(&line) {
+                        event.as_ref().filter(|_| action.command == "test").and_then(|e| e.summary_line())
+                    } else {
+                        Some(line)
+                    };
+                    handle.clone().upgrade_in_event_loop(move |h| {
+                        if let Some(line) = line {
+                            console::push_line(&h, console::Stream::Stdout, console::parse_ansi(&line));
+                        }
+                        if let Some(event) = event {
+                            test_runner::apply_test_event(&h, event);
+                        }
+                        if let Some(measurement) = measurement {
+                            bench::push_measurement(&h, &measurement);
+                        }
+                    }).unwrap();
+                    continue;
+                }
                 let mut deserializer = serde_json::Deserializer::from_str(&line);
                 deserializer.disable_recursion_limit();
                 let msg = cargo_metadata::Message::deserialize(&mut deserializer).unwrap_or(cargo_metadata::Message::TextLine(line));
 
-                if let Some(diag) = cargo_message_to_diag(msg) {
+                if let cargo_metadata::Message::BuildFinished(_) = msg {
+                    build_finished = true;
+                }
+                if let cargo_metadata::Message::CompilerArtifact(_) | cargo_metadata::Message::BuildScriptExecuted(_) = msg {
+                    progress.done += 1;
+                    // The estimation of the total may be a bit short
+                    progress.total = progress.total.max(progress.done);
+                    let progress = progress.clone();
+                    handle.clone().upgrade_in_event_loop(move |h| h.set_build_progress(progress)).unwrap();
+                }
+
+                let fix = match &msg {
+                    cargo_metadata::Message::CompilerMessage(m) => Some(fixes.borrow_mut().add(&m.message)),
+                    _ => None,
+                };
+                if let Some(mut diag) = cargo_message_to_diag(msg) {
+                    if let Some((id, fixable)) = fix {
+                        diag.id = id;
+                        diag.fix = match fixable {
+                            Fixable::No => 0,
+                            Fixable::MaybeIncorrect => 1,
+                            Fixable::MachineApplicable => 2,
+                        };
+                    }
+                    diagnostics.push(diag.clone());
                     handle.clone().upgrade_in_event_loop(move |h|{
                         let model_handle = h.get_diagnostics();
-                        let model = model_handle.as_any().downcast_ref::<VecModel<Diag>>().unwrap();
+                        let model = model_handle.as_any().downcast_ref::<DiagnosticsModel>().unwrap();
                         model.push(diag);
                     }).unwrap();
                 }
@@ -430,27 +1295,54 @@ async fn run_cargo(
         }
     }
 
+    let status = res.wait().await?;
+    process_group.0 = None;
+    let run = history::Run {
+        configuration,
+        command_line,
+        invocation,
+        timestamp,
+        exit_code: status.code(),
+        cancelled,
+        duration: start_time.elapsed(),
+        diagnostics,
+    };
+    let status_text: SharedString = if cancelled {
+        "Cancelled".into()
+    } else if status.success() {
+        "Finished".into()
+    } else if !cargo_error.is_empty() {
+        cargo_error.join("\n").into()
+    } else {
+        format!("Failed ({})", process::describe_exit_status(&status)).into()
+    };
+
+    progress.current = Default::default();
+    progress.elapsed = format_duration(start_time.elapsed()).into();
+    let no_bench_results = is_bench && measurements.is_empty();
+    let fix_needs_allow_dirty = cargo_error.iter().any(|l| l.contains("--allow-dirty"));
     handle
         .upgrade_in_event_loop(move |h| {
-            h.set_status("Finished".into());
+            h.set_build_progress(progress);
+            h.set_status(status_text);
+            h.set_fix_needs_allow_dirty(fix_needs_allow_dirty);
             let model_handle = h.get_diagnostics();
             let model = model_handle
                 .as_any()
-                .downcast_ref::<VecModel<Diag>>()
+                .downcast_ref::<DiagnosticsModel>()
                 .unwrap();
 
-            if model.row_count() == 0 {
+            // When watching, the pane stays visible to show that the sources are fine
+            if model.is_empty()
+                && !h.get_console_active()
+                && !h.get_watch()
+                && !fix_needs_allow_dirty
+            {
                 h.set_build_pane_visible(false);
             }
 
-            let error_count = model
-                .iter()
-                .filter(|diagnostic| diagnostic.level == 1)
-                .count();
-            let warning_count = model
-                .iter()
-                .filter(|diagnostic| diagnostic.level == 2)
-                .count();
+            let error_count = model.level_count(1);
+            let warning_count = model.level_count(2);
 
             let result = if error_count == 0 && warning_count == 0 {
                 "✅".into()
@@ -463,24 +1355,152 @@ async fn run_cargo(
                 "check" => h.set_check_results(result),
                 _ => {}
             }
+            if no_bench_results {
+                h.global::<BenchData>()
+                    .set_status("No benchmark result found in the output".into());
+            }
         })
         .unwrap();
 
-    Ok(())
+    Ok(RunOutcome {
+        measurements,
+        run: Some(run),
+    })
+}
+
+/// Whether a line printed by cargo after the build is one of its own status lines, rather than
+/// the output of the program (e.g. `Running unittests src/lib.rs`)
+fn is_cargo_status(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("Running ") || line.starts_with("Doc-tests ")
+}
+
+/// Estimate how many units cargo is going to build for the action: the packages in the
+/// dependency graph of the selected package, plus the compilation and the execution of their
+/// build scripts.
+fn count_build_units(metadata: &Metadata, action: &Action) -> i32 {
+    let Some(resolve) = &metadata.resolve else {
+        return 0;
+    };
+    let map: HashMap<_, _> = resolve.nodes.iter().map(|n| (&n.id, n)).collect();
+    let with_dev_dependencies = matches!(action.command.as_str(), "test" | "bench" | "nextest");
+    let mut stack: Vec<(&PackageId, bool)> = metadata
+        .workspace_members
+        .iter()
+        .filter(|m| action.package.is_empty() || action.package == metadata[m].name.as_str())
+        .map(|m| (m, true))
+        .collect();
+    let mut visited = HashSet::new();
+    while let Some((id, is_member)) = stack.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        for d in map.get(id).map_or(&[][..], |n| &n.deps[..]) {
+            // Only the dev-dependencies of the selected packages are built
+            if d.dep_kinds.is_empty()
+                || d.dep_kinds.iter().any(|k| {
+                    k.kind != DependencyKind::Development || (is_member && with_dev_dependencies)
+                })
+            {
+                stack.push((&d.pkg, false));
+            }
+        }
+    }
+    visited
+        .iter()
+        .map(|id| {
+            let has_build_script = metadata[id]
+                .targets
+                .iter()
+                .any(|t| t.kind.contains(&TargetKind::CustomBuild));
+            if has_build_script {
+                3
+            } else {
+                1
+            }
+        })
+        .sum()
+}
+
+fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Create a command that runs cargo with the given rustup toolchain (or the default one if empty)
+pub fn cargo_command(toolchain: &str) -> tokio::process::Command {
+    if toolchain.is_empty() {
+        let cargo_path = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+        tokio::process::Command::new(cargo_path)
+    } else {
+        // `CARGO` points to the cargo binary of one specific toolchain, so go through the
+        // rustup proxy instead.
+        let mut command = tokio::process::Command::new("cargo");
+        command.env("RUSTUP_TOOLCHAIN", toolchain);
+        command
+    }
 }
 
-pub fn cargo_command() -> tokio::process::Command {
-    let cargo_path = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
-    tokio::process::Command::new(cargo_path)
+/// The name of the package, from its id: `name 0.1.0 (source)`, or a package id spec like
+/// `registry+https://github.com/rust-lang/crates.io-index#name@0.1.0`, where the name is omitted
+/// when it is the last component of the path, as in `path+file:///path/to/name#0.1.0`
+pub fn package_name(id: &PackageId) -> &str {
+    let repr = id.repr.as_str();
+    if let Some((name, _)) = repr.split_once(' ') {
+        return name;
+    }
+    match repr.rsplit_once('#') {
+        Some((_, fragment)) if fragment.contains('@') => {
+            fragment.split_once('@').map_or(fragment, |(name, _)| name)
+        }
+        Some((url, _)) => {
+            let path = url.split_once('?').map_or(url, |(path, _)| path);
+            path.trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or(path)
+        }
+        None => repr,
+    }
 }
 
 fn cargo_message_to_diag(msg: cargo_metadata::Message) -> Option<Diag> {
     match msg {
         cargo_metadata::Message::CompilerMessage(msg) => {
+            let crate_name = package_name(&msg.package_id).to_owned();
+            let msg = msg.message;
+            let primary_span = msg.spans.iter().find(|s| s.is_primary);
+            let children = msg
+                .children
+                .iter()
+                .map(|child| {
+                    let level = match child.level {
+                        DiagnosticLevel::Help => "help",
+                        DiagnosticLevel::Warning => "warning",
+                        DiagnosticLevel::Error => "error",
+                        _ => "note",
+                    };
+                    let suggestions = child
+                        .spans
+                        .iter()
+                        .filter_map(|s| s.suggested_replacement.as_deref())
+                        .map(|r| format!("`{}`", r))
+                        .join(", ");
+                    if suggestions.is_empty() {
+                        format!("{}: {}", level, child.message)
+                    } else {
+                        format!("{}: {} {}", level, child.message, suggestions)
+                    }
+                })
+                .join("\n");
             let diag = Diag {
-                short: msg.message.message.into(),
-                expanded: msg.message.rendered.unwrap_or_default().into(),
-                level: match msg.message.level {
+                short: msg.message.into(),
+                expanded: msg.rendered.unwrap_or_default().into(),
+                level: match msg.level {
                     DiagnosticLevel::Error => 1,
                     DiagnosticLevel::Warning => 2,
                     DiagnosticLevel::FailureNote => 3,
@@ -488,14 +1508,24 @@ fn cargo_message_to_diag(msg: cargo_metadata::Message) -> Option<Diag> {
                     DiagnosticLevel::Help => 3,
                     _ => 0,
                 },
+                file: primary_span
+                    .map(|s| s.file_name.as_str())
+                    .unwrap_or_default()
+                    .into(),
+                line: primary_span.map_or(0, |s| s.line_start as i32),
+                column: primary_span.map_or(0, |s| s.column_start as i32),
+                code: msg.code.map(|c| c.code).unwrap_or_default().into(),
+                excerpt: primary_span.map(span_excerpt).unwrap_or_default().into(),
+                children: children.into(),
+                crate_name: crate_name.into(),
+                ..Default::default()
             };
             Some(diag)
         }
         cargo_metadata::Message::TextLine(line) => {
             let diag = Diag {
                 short: line.into(),
-                expanded: Default::default(),
-                level: 0,
+                ..Default::default()
             };
             Some(diag)
         }
@@ -503,6 +1533,56 @@ fn cargo_message_to_diag(msg: cargo_metadata::Message) -> Option<Diag> {
     }
 }
 
+fn apply_fixes(fixes: &mut FixStore, ids: &[i32], root: &Path, handle: slint::Weak<CargoUI>) {
+    let result = fixes.apply(ids, root);
+    handle
+        .upgrade_in_event_loop(move |h| match result {
+            Ok(done) => {
+                let model_handle = h.get_diagnostics();
+                if let Some(model) = model_handle.as_any().downcast_ref::<DiagnosticsModel>() {
+                    model.update(|diag| {
+                        if diag.fix != 0 && done.contains(&diag.id) {
+                            diag.fix = 3;
+                        }
+                    });
+                }
+                h.set_status(if done.is_empty() {
+                    "No fix could be applied".into()
+                } else {
+                    format!("Applied {} fixes", done.len()).into()
+                });
+            }
+            Err(e) => h.set_status(format!("{:#}", e).into()),
+        })
+        .unwrap();
+}
+
+/// Render the source lines of a span, with the highlighted part underlined, like rustc does
+fn span_excerpt(span: &DiagnosticSpan) -> String {
+    let width = span.line_end.to_string().len();
+    let mut lines = Vec::new();
+    for (i, line) in span.text.iter().enumerate() {
+        lines.push(format!(
+            "{:>width$} | {}",
+            span.line_start + i,
+            line.text.trim_end()
+        ));
+        if line.highlight_end > line.highlight_start {
+            lines.push(format!(
+                "{:>width$} | {}{}",
+                "",
+                " ".repeat(line.highlight_start.saturating_sub(1)),
+                "^".repeat(line.highlight_end - line.highlight_start),
+            ));
+        }
+    }
+    if let (Some(label), Some(last)) = (&span.label, lines.last_mut()) {
+        last.push(' ');
+        last.push_str(label);
+    }
+    lines.join("\n")
+}
+
 fn default_manifest() -> PathBuf {
     // skip the "ui" arg in case we are invoked with `cargo ui`
     dunce::canonicalize(
@@ -517,7 +1597,11 @@ fn default_manifest() -> PathBuf {
     .unwrap_or_default()
 }
 
-async fn read_metadata(manifest: Manifest, handle: slint::Weak<CargoUI>) -> Option<Metadata> {
+async fn read_metadata(
+    manifest: Manifest,
+    toolchain: SharedString,
+    handle: slint::Weak<CargoUI>,
+) -> Option<Metadata> {
     let manifest_str = manifest
         .path_to_cargo_toml()
         .to_string_lossy()
@@ -534,6 +1618,13 @@ async fn read_metadata(manifest: Manifest, handle: slint::Weak<CargoUI>) -> Opti
 
     let mut cmd = cargo_metadata::MetadataCommand::new();
     cmd.manifest_path(manifest.path_to_cargo_toml());
+    if let Some(directory) = manifest.directory() {
+        cmd.current_dir(directory);
+    }
+    if !toolchain.is_empty() {
+        cmd.cargo_path("cargo")
+            .env("RUSTUP_TOOLCHAIN", toolchain.as_str());
+    }
     match cmd.exec() {
         Ok(metadata) => {
             handle
@@ -554,6 +1645,7 @@ async fn read_metadata(manifest: Manifest, handle: slint::Weak<CargoUI>) -> Opti
     }
 }
 
+#[allow(clippy::manual_contains)]
 fn apply_metadata(
     metadata: &Metadata,
     crates_index: Option<&crates_index::GitIndex>,
@@ -564,6 +1656,7 @@ fn apply_metadata(
     let mut packages = vec![SharedString::default()]; // keep one empty row
     let mut run_target = Vec::new();
     let mut test_target = Vec::new();
+    let mut bench_target = vec![SharedString::default()]; // the empty row runs all the benchmarks
     let mut features: Option<Vec<Feature>> = None;
     if !package.is_empty()
         && !metadata
@@ -623,6 +1716,8 @@ fn apply_metadata(
                 run_target.push(SharedString::from(format!("{} (example)", t.name).as_str()));
             } else if t.kind.iter().any(|x| *x == TargetKind::Test) {
                 test_target.push(SharedString::from(t.name.as_str()));
+            } else if t.kind.contains(&TargetKind::Bench) {
+                bench_target.push(SharedString::from(t.name.as_str()));
             }
         }
     }
@@ -645,6 +1740,9 @@ fn apply_metadata(
             h.set_extra_test(ModelRc::from(
                 Rc::new(VecModel::from(test_target)) as Rc<dyn Model<Data = SharedString>>
             ));
+            h.set_extra_bench(ModelRc::from(
+                Rc::new(VecModel::from(bench_target)) as Rc<dyn Model<Data = SharedString>>
+            ));
             if let Some(features) = features {
                 h.set_has_features(!features.is_empty());
                 h.set_enable_default_features(true);
@@ -660,6 +1758,7 @@ fn apply_metadata(
     if let Some(resolve) = &metadata.resolve {
         let mut duplicates = HashSet::new();
         let map: HashMap<_, _> = resolve.nodes.iter().map(|n| (n.id.clone(), n)).collect();
+        let workspace_dependencies = manifest_edits::workspace_dependencies(metadata);
         for m in &metadata.workspace_members {
             if !package.is_empty() && package != metadata[m].name.as_str() {
                 continue;
@@ -675,17 +1774,87 @@ fn apply_metadata(
                 &map,
                 0,
             );
+            if let Some(member) = depgraph_tree.last() {
+                for dependency in &member.children {
+                    let mut node = dependency.node.borrow_mut();
+                    let name = node.crate_name.to_string();
+                    node.shared = workspace_dependencies.users.get(&name) > Some(&1);
+                    node.inherited = workspace_dependencies
+                        .inherited
+                        .contains(&(m.clone(), name));
+                }
+            }
         }
     }
 
     handle
         .upgrade_in_event_loop(move |h| {
+            // Keep the nodes that the user opened or closed when the metadata is reloaded
+            let previous = h.global::<DependencyData>().get_model();
+            if let Some(previous) = previous.as_any().downcast_ref::<DepGraphModel>() {
+                DepGraphModel::restore_open_states(
+                    &depgraph_tree,
+                    &previous.open_states(),
+                    &mut Vec::new(),
+                );
+            }
             let model = DepGraphModel::from(depgraph_tree);
             h.global::<DependencyData>().set_model(ModelRc::new(model))
         })
         .unwrap();
 }
 
+fn load_settings(metadata: &Metadata, handle: slint::Weak<CargoUI>) -> Option<WorkspaceSettings> {
+    match WorkspaceSettings::load(metadata.workspace_root.as_std_path()) {
+        Ok(settings) => {
+            let (runner, profile) = (settings.test_runner(), settings.nextest_profile());
+            handle
+                .clone()
+                .upgrade_in_event_loop(move |h| test_runner::apply_settings(&h, runner, profile))
+                .unwrap();
+            apply_run_configurations(&settings, handle);
+            Some(settings)
+        }
+        Err(e) => {
+            handle
+                .upgrade_in_event_loop(move |h| {
+                    h.set_status(format!("{:#}", e).into());
+                })
+                .unwrap();
+            None
+        }
+    }
+}
+
+/// Write the settings, and show the run configurations that may have changed
+fn save_settings(settings: &WorkspaceSettings, handle: slint::Weak<CargoUI>) {
+    if let Err(e) = settings.save() {
+        handle
+            .clone()
+            .upgrade_in_event_loop(move |h| {
+                h.set_status(format!("{:#}", e).into());
+            })
+            .unwrap();
+    }
+    apply_run_configurations(settings, handle);
+}
+
+fn apply_run_configurations(settings: &WorkspaceSettings, handle: slint::Weak<CargoUI>) {
+    let configurations = settings.run_configurations();
+    handle
+        .upgrade_in_event_loop(move |h| {
+            let names: Vec<SharedString> = configurations.iter().map(|c| c.name.clone()).collect();
+            let data = h.global::<RunConfigurationData>();
+            data.set_names(ModelRc::from(
+                Rc::new(VecModel::from(names)) as Rc<dyn Model<Data = SharedString>>
+            ));
+            data.set_configurations(ModelRc::from(
+                Rc::new(VecModel::from(configurations)) as Rc<dyn Model<Data = RunConfiguration>>
+            ));
+        })
+        .unwrap();
+}
+
 fn show_open_dialog(manifest: Manifest) -> Manifest {
     let mut dialog = rfd::FileDialog::new();
     dialog = dialog.set_title("Select a manifest");
@@ -705,6 +1874,7 @@ struct TreeNode {
     children: Vec<TreeNode>,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_dep_tree(
     package_id: &PackageId,
     node_dep: Option<&cargo_metadata::NodeDep>,
@@ -719,12 +1889,31 @@ fn build_dep_tree(
     let package = &metadata[package_id];
     let duplicated = duplicates.contains(package_id);
     // We only consider indentation ==1 because `idx.crate_` is a bit too slow to do for every crate
-    let outdated = indentation == 1
-        && crates_index
-            .and_then(|idx| idx.crate_(&package.name))
-            .and_then(|c| c.highest_normal_version().cloned())
-            .and_then(|v| Version::from_str(v.version()).ok())
-            .map_or(false, |latest| latest > package.version);
+    let (latest_compatible, latest_incompatible) = if indentation == 1 {
+        let krate = crates_index.and_then(|idx| idx.crate_(&package.name));
+        let req = node_dep
+            .and_then(|n| n.dep_kinds.first())
+            .and_then(|k| {
+                dependency_features::find_dependency(
+                    metadata,
+                    parent_package,
+                    &package.name,
+                    k.kind,
+                )
+                .ok()
+            })
+            .map(|(_, d)| d.req.clone())
+            .unwrap_or_else(|| {
+                let v = &package.version;
+                VersionReq::parse(&format!("^{}.{}.{}", v.major, v.minor, v.patch)).unwrap()
+            });
+        krate
+            .map(|k| dependency_versions::upgrades(&k, &package.version, &req))
+            .unwrap_or_default()
+    } else {
+        Default::default()
+    };
+    let outdated = latest_compatible.is_some() || latest_incompatible.is_some();
     let dep_kind = node_dep
         .filter(|n| {
             !n.dep_kinds
@@ -747,9 +1936,16 @@ fn build_dep_tree(
             version: package.version.to_string().into(),
             crate_name: package.name.as_str().into(),
             outdated,
+            latest_compatible: latest_compatible
+                .map(|v| v.to_string().into())
+                .unwrap_or_default(),
+            latest_incompatible: latest_incompatible
+                .map(|v| v.to_string().into())
+                .unwrap_or_default(),
             duplicated,
             dep_kind,
             parent_package: parent_package.clone(),
+            ..Default::default()
         }
         .into(),
         children: Default::default(),
@@ -820,6 +2016,41 @@ impl DepGraphModel {
         }
     }
 
+    /// Whether each node is open, by the names of the crates from the root to that node
+    fn open_states(&self) -> HashMap<Vec<SharedString>, bool> {
+        fn collect(
+            nodes: &[TreeNode],
+            path: &mut Vec<SharedString>,
+            states: &mut HashMap<Vec<SharedString>, bool>,
+        ) {
+            for n in nodes {
+                let node = n.node.borrow();
+                path.push(node.crate_name.clone());
+                states.insert(path.clone(), node.open);
+                collect(&n.children, path, states);
+                path.pop();
+            }
+        }
+        let mut states = HashMap::new();
+        collect(&self.tree, &mut Vec::new(), &mut states);
+        states
+    }
+
+    fn restore_open_states(
+        nodes: &[TreeNode],
+        states: &HashMap<Vec<SharedString>, bool>,
+        path: &mut Vec<SharedString>,
+    ) {
+        for n in nodes {
+            path.push(n.node.borrow().crate_name.clone());
+            if let Some(open) = states.get(path) {
+                n.node.borrow_mut().open = *open;
+            }
+            Self::restore_open_states(&n.children, states, path);
+            path.pop();
+        }
+    }
+
     fn relayout(&self) {
         let mut cache = self.cache.borrow_mut();
         self.notify.row_removed(0, cache.len());
@@ -854,6 +2085,10 @@ impl slint::Model for DepGraphModel {
         self.get_node(&self.cache.borrow()[row]).node.replace(data);
         self.relayout();
     }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -879,6 +2114,7 @@ impl Manifest {
 }
 
 impl FeatureSettings {
+    #[allow(clippy::nonminimal_bool)]
     pub fn new(ui: &CargoUI) -> Self {
         let enable_default_features = ui.get_enable_default_features();
         let enabled_features = ui
@@ -900,7 +2136,27 @@ impl FeatureSettings {
         }
     }
 
-    fn to_args(&self, process: &mut tokio::process::Command) {
+    /// The features saved in a run configuration
+    pub fn from_configuration(configuration: &RunConfiguration) -> Self {
+        Self {
+            enabled_features: configuration
+                .features
+                .split(',')
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .map(SharedString::from)
+                .collect(),
+            enable_default_features: configuration.default_features,
+        }
+    }
+
+    /// Save the features in a run configuration
+    pub fn store(&self, configuration: &mut RunConfiguration) {
+        configuration.features = self.enabled_features.iter().join(",").into();
+        configuration.default_features = self.enable_default_features;
+    }
+
+    pub fn to_args(&self, process: &mut tokio::process::Command) {
         if !self.enable_default_features {
             process.arg("--no-default-features");
         }
@@ -912,7 +2168,7 @@ impl FeatureSettings {
     }
 }
 
-fn to_table_name(dep_kind: DependencyKind) -> &'static str {
+pub fn to_table_name(dep_kind: DependencyKind) -> &'static str {
     match dep_kind {
         DependencyKind::Development => "dev-dependencies",
         DependencyKind::Build => "build-dependencies",
@@ -920,43 +2176,79 @@ fn to_table_name(dep_kind: DependencyKind) -> &'static str {
     }
 }
 
-fn dependency_remove(pkg: &Path, dependency: &str, dep_kind: DependencyKind) -> anyhow::Result<()> {
-    let manifest_contents = std::fs::read_to_string(pkg)
-        .with_context(|| format!("Failed to load '{}'", pkg.display()))?;
-    let mut document: toml_edit::DocumentMut = manifest_contents.parse()?;
-    let table_name = to_table_name(dep_kind);
-    let dependencies = &mut document[table_name];
-    let removed = !std::mem::take(&mut dependencies[dependency]).is_none();
-    if !removed {
-        anyhow::bail!("'{}' was not in [{}]", dependency, dep_kind);
-    }
-    std::fs::write(pkg, document.to_string().as_bytes())
-        .with_context(|| format!("Failed to write '{}'", pkg.display()))
+/// Remove the dependency from the manifest of `parent_package`, including from a
+/// `[target.'cfg(..)'.dependencies]` table
+fn dependency_remove(
+    metadata: &Metadata,
+    parent_package: &str,
+    crate_name: &str,
+    dep_kind: DependencyKind,
+) -> anyhow::Result<()> {
+    let (parent, dependency) =
+        dependency_features::find_dependency(metadata, parent_package, crate_name, dep_kind)?;
+    let manifest = parent.manifest_path.as_std_path();
+    let target = dependency.target.as_ref().map(|t| t.to_string());
+    let key = dependency.rename.as_deref().unwrap_or(crate_name);
+    let mut edits = ManifestEdits::default();
+    // fails if it is not there
+    edits.entry(manifest, dep_kind, target.as_deref(), key)?;
+    dependency_table(edits.document(manifest)?, dep_kind, target.as_deref())
+        .as_table_like_mut()
+        .context("Could not understand the manifest")?
+        .remove(key);
+    edits.write()?;
+    Ok(())
 }
 
-fn dependency_upgrade_to_version(
-    pkg: &Path,
-    dependency: &str,
-    version: &str,
+/// The `[dependencies]` table of that kind, or the one of `[target.<target>]`
+pub fn dependency_table<'a>(
+    document: &'a mut toml_edit::DocumentMut,
     dep_kind: DependencyKind,
-) -> anyhow::Result<()> {
-    let manifest_contents = std::fs::read_to_string(pkg)
-        .with_context(|| format!("Failed to load '{}'", pkg.display()))?;
-    let mut document: toml_edit::DocumentMut = manifest_contents.parse()?;
+    target: Option<&str>,
+) -> &'a mut toml_edit::Item {
     let table_name = to_table_name(dep_kind);
-    let dep = &mut document[table_name][dependency];
-    if dep.is_none() {
-        anyhow::bail!("'{}' was not in [{}]", dependency, dep_kind);
+    match target {
+        Some(target) => &mut document["target"][target][table_name],
+        None => &mut document[table_name],
     }
+}
+
+/// Change the requirement of a dependency, whether it is only given by its version or a table
+pub fn set_dependency_version(dep: &mut toml_edit::Item, version: &str) -> anyhow::Result<()> {
     if dep.is_str() {
         *dep = toml_edit::Item::Value(version.into());
+    } else if dep.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+        anyhow::bail!("The version is inherited from the workspace");
     } else if dep.is_table_like() {
         dep["version"] = toml_edit::Item::Value(version.into());
     } else {
         anyhow::bail!("Could not understand the manifest");
     }
-    std::fs::write(pkg, document.to_string().as_bytes())
-        .with_context(|| format!("Failed to write '{}'", pkg.display()))
+    Ok(())
+}
+
+fn dependency_upgrade_to_version(
+    metadata: &Metadata,
+    parent_package: &str,
+    crate_name: &str,
+    version: &str,
+    dep_kind: DependencyKind,
+) -> anyhow::Result<()> {
+    let (parent, dependency) =
+        dependency_features::find_dependency(metadata, parent_package, crate_name, dep_kind)?;
+    let target = dependency.target.as_ref().map(|t| t.to_string());
+    let key = dependency.rename.as_deref().unwrap_or(crate_name);
+    let mut edits = ManifestEdits::default();
+    let dep = edits.dependency(
+        parent.manifest_path.as_std_path(),
+        metadata.workspace_root.as_std_path(),
+        dep_kind,
+        target.as_deref(),
+        key,
+    )?;
+    set_dependency_version(dep, version)?;
+    edits.write()?;
+    Ok(())
 }
 
 fn dependency_add(
@@ -983,6 +2275,42 @@ fn dependency_add(
         .with_context(|| format!("Failed to write '{}'", pkg.display()))
 }
 
+/// Run `cargo update` to change the version of one package in the lockfile, without touching
+/// the manifests
+async fn update_lockfile(
+    manifest: Manifest,
+    toolchain: SharedString,
+    spec: String,
+    precise: SharedString,
+    handle: slint::Weak<CargoUI>,
+) {
+    let status = format!("Updating {} to {}...", spec, precise);
+    handle
+        .clone()
+        .upgrade_in_event_loop(move |h| h.set_status(status.into()))
+        .unwrap();
+    let mut cmd = cargo_command(&toolchain);
+    cmd.arg("update")
+        .arg("--manifest-path")
+        .arg(manifest.path_to_cargo_toml())
+        .arg("-p")
+        .arg(&spec)
+        .arg("--precise")
+        .arg(precise.as_str())
+        .kill_on_drop(true);
+    if let Some(directory) = manifest.directory() {
+        cmd.current_dir(directory);
+    }
+    let status = match cmd.output().await {
+        Ok(output) if output.status.success() => format!("Updated {} to {}", spec, precise),
+        Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        Err(e) => format!("Failed to run cargo update: {}", e),
+    };
+    handle
+        .upgrade_in_event_loop(move |h| h.set_status(status.into()))
+        .unwrap();
+}
+
 use crate::install::*;
 
 async fn install_completion(idx_path: PathBuf, query: SharedString, handle: slint::Weak<CargoUI>) {
@@ -1022,3 +2350,39 @@ async fn install_completion(idx_path: PathBuf, query: SharedString, handle: slin
         })
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_name_from_id() {
+        let name = |repr: &str| {
+            package_name(&PackageId {
+                repr: repr.to_owned(),
+            })
+            .to_owned()
+        };
+        assert_eq!(
+            name("registry+https://github.com/rust-lang/crates.io-index#serde@1.0.200"),
+            "serde"
+        );
+        assert_eq!(name("path+file:///home/me/cargo-ui#0.3.3"), "cargo-ui");
+        assert_eq!(
+            name("path+file:///home/me/ws/core#my-core@0.1.0"),
+            "my-core"
+        );
+        assert_eq!(
+            name("git+https://github.com/slint-ui/slint?branch=master#i-slint-core@1.6.0"),
+            "i-slint-core"
+        );
+        assert_eq!(
+            name("git+https://github.com/slint-ui/slint?rev=abcdef#1.6.0"),
+            "slint"
+        );
+        assert_eq!(
+            name("cargo-ui 0.3.3 (path+file:///home/me/cargo-ui)"),
+            "cargo-ui"
+        );
+    }
+}
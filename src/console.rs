@@ -0,0 +1,175 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{CargoUI, ConsoleLine, ConsoleSpan};
+use slint::{Color, Model, ModelRc, VecModel};
+use std::rc::Rc;
+
+/// Number of lines of the program output that are kept
+const MAX_LINES: usize = 10_000;
+
+/// Where a line of the console comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+    /// What the user typed
+    Stdin,
+}
+
+/// Start a new, empty, console. `active` tells whether the console is shown at all.
+pub fn reset(ui: &CargoUI, active: bool) {
+    ui.set_console_active(active);
+    ui.set_console_input_open(active);
+    ui.set_console_lines(ModelRc::from(
+        Rc::new(VecModel::<ConsoleLine>::default()) as Rc<dyn Model<Data = ConsoleLine>>
+    ));
+}
+
+/// Append a line to the console of the UI, dropping the oldest ones past the scrollback limit
+pub fn push_line(ui: &CargoUI, stream: Stream, spans: Vec<ConsoleSpan>) {
+    let model_handle = ui.get_console_lines();
+    let Some(model) = model_handle
+        .as_any()
+        .downcast_ref::<VecModel<ConsoleLine>>()
+    else {
+        return;
+    };
+    if model.row_count() >= MAX_LINES {
+        model.remove(0);
+    }
+    model.push(ConsoleLine {
+        spans: ModelRc::new(VecModel::from(spans)),
+        stream: match stream {
+            Stream::Stdout => 0,
+            Stream::Stderr => 1,
+            Stream::Stdin => 2,
+        },
+    });
+}
+
+/// The 16 colors of the terminal, in the order of the SGR codes
+const PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xcd, 0x31, 0x31),
+    (0x0d, 0xbc, 0x79),
+    (0xe5, 0xe5, 0x10),
+    (0x24, 0x72, 0xc8),
+    (0xbc, 0x3f, 0xbc),
+    (0x11, 0xa8, 0xcd),
+    (0xe5, 0xe5, 0xe5),
+    (0x66, 0x66, 0x66),
+    (0xf1, 0x4c, 0x4c),
+    (0x23, 0xd1, 0x8b),
+    (0xf5, 0xf5, 0x43),
+    (0x3b, 0x8e, 0xea),
+    (0xd6, 0x70, 0xd6),
+    (0x29, 0xb8, 0xdb),
+    (0xff, 0xff, 0xff),
+];
+
+fn color_256(n: u16) -> Option<Color> {
+    let n = u8::try_from(n).ok()?;
+    let (r, g, b) = match n {
+        0..=15 => PALETTE[n as usize],
+        16..=231 => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let n = n - 16;
+            (level(n / 36), level((n / 6) % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + (n - 232) * 10;
+            (gray, gray, gray)
+        }
+    };
+    Some(Color::from_rgb_u8(r, g, b))
+}
+
+/// The state of the SGR attributes while parsing a line
+#[derive(Default, Clone, Copy)]
+struct Style {
+    color: Option<Color>,
+    bold: bool,
+}
+
+impl Style {
+    /// Apply the parameters of a `ESC [ ... m` sequence
+    fn apply(&mut self, params: &str) {
+        let mut params = params.split(';').map(|p| p.parse::<u16>().unwrap_or(0));
+        while let Some(p) = params.next() {
+            match p {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                30..=37 => self.color = color_256(p - 30),
+                90..=97 => self.color = color_256(p - 90 + 8),
+                39 => self.color = None,
+                38 => match params.next() {
+                    Some(5) => self.color = params.next().and_then(color_256),
+                    Some(2) => {
+                        let mut c = || params.next().unwrap_or(0).min(255) as u8;
+                        self.color = Some(Color::from_rgb_u8(c(), c(), c()));
+                    }
+                    _ => {}
+                },
+                // background colors and other attributes are not rendered
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Split a line of output into spans of the same style, interpreting the ANSI color codes.
+/// The other escape sequences are removed.
+pub fn parse_ansi(line: &str) -> Vec<ConsoleSpan> {
+    let mut spans = Vec::new();
+    let mut style = Style::default();
+    let mut text = String::new();
+    let mut flush = |text: &mut String, style: Style| {
+        if !text.is_empty() {
+            spans.push(ConsoleSpan {
+                text: std::mem::take(text).into(),
+                has_color: style.color.is_some(),
+                color: style.color.unwrap_or_default(),
+                bold: style.bold,
+            });
+        }
+    };
+
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                if chars.next_if_eq(&'[').is_none() {
+                    // not a CSI sequence: skip the next character
+                    chars.next();
+                    continue;
+                }
+                let mut params = String::new();
+                let mut command = None;
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        command = Some(c);
+                        break;
+                    }
+                    params.push(c);
+                }
+                if command == Some('m') {
+                    flush(&mut text, style);
+                    style.apply(&params);
+                }
+            }
+            '\r' => {}
+            '\t' => text.push_str("    "),
+            c => text.push(c),
+        }
+    }
+    flush(&mut text, style);
+    spans
+}
+
+/// Remove the ANSI escape sequences of a line
+pub fn strip_ansi(line: &str) -> String {
+    parse_ansi(line).iter().map(|s| s.text.as_str()).collect()
+}
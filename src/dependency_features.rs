@@ -0,0 +1,202 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{CargoUI, DependencyData, DependencyFeature};
+use crate::manifest_edits::ManifestEdits;
+use anyhow::Context;
+use cargo_metadata::{Dependency, DependencyKind, Metadata, Package};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// The package `parent_package` and its dependency `crate_name`, as declared in its manifest
+pub fn find_dependency<'a>(
+    metadata: &'a Metadata,
+    parent_package: &str,
+    crate_name: &str,
+    dep_kind: DependencyKind,
+) -> anyhow::Result<(&'a Package, &'a Dependency)> {
+    let parent = metadata
+        .packages
+        .iter()
+        .find(|p| p.id.repr == parent_package)
+        .with_context(|| format!("Unknown package '{}'", parent_package))?;
+    let dependency = parent
+        .dependencies
+        .iter()
+        .find(|d| d.name == crate_name && d.kind == dep_kind)
+        .with_context(|| format!("'{}' is not a dependency of '{}'", crate_name, parent.name))?;
+    Ok((parent, dependency))
+}
+
+/// The package that the dependency `crate_name` of `parent` resolved to
+pub fn resolved_package<'a>(
+    metadata: &'a Metadata,
+    parent: &Package,
+    crate_name: &str,
+    dep_kind: DependencyKind,
+) -> anyhow::Result<&'a Package> {
+    let resolve = metadata
+        .resolve
+        .as_ref()
+        .context("The dependencies are not resolved")?;
+    resolve
+        .nodes
+        .iter()
+        .find(|n| n.id == parent.id)
+        .and_then(|n| {
+            n.deps.iter().find(|d| {
+                metadata[&d.pkg].name == crate_name
+                    && d.dep_kinds.iter().any(|k| k.kind == dep_kind)
+            })
+        })
+        .map(|d| &metadata[&d.pkg])
+        .with_context(|| format!("'{}' is not in the dependency graph", crate_name))
+}
+
+/// Open the feature editor with the features declared by the dependency, those requested in the
+/// manifest, and those enabled in the resolved graph (possibly by another crate)
+pub fn show(
+    metadata: &Metadata,
+    parent_package: SharedString,
+    crate_name: SharedString,
+    dep_kind: DependencyKind,
+    handle: slint::Weak<CargoUI>,
+) -> anyhow::Result<()> {
+    let (parent, dependency) = find_dependency(metadata, &parent_package, &crate_name, dep_kind)?;
+    let package = resolved_package(metadata, parent, &crate_name, dep_kind)?;
+    let enabled: HashSet<&str> = metadata
+        .resolve
+        .iter()
+        .flat_map(|r| r.nodes.iter())
+        .find(|n| n.id == package.id)
+        .map(|n| n.features.iter().map(|f| f.as_str()).collect())
+        .unwrap_or_default();
+    let default: HashSet<&str> = package
+        .features
+        .get("default")
+        .map(|f| f.iter().map(|f| f.as_str()).collect())
+        .unwrap_or_default();
+
+    let features: Vec<DependencyFeature> = package
+        .features
+        .iter()
+        .filter(|(name, _)| name.as_str() != "default")
+        .map(|(name, enables)| DependencyFeature {
+            name: name.as_str().into(),
+            enables: enables.join(", ").into(),
+            default: default.contains(name.as_str()),
+            requested: dependency.features.contains(name),
+            enabled: enabled.contains(name.as_str()),
+        })
+        .collect();
+    let default_features = dependency.uses_default_features;
+    let dep_kind = match dep_kind {
+        DependencyKind::Normal => String::new(),
+        kind => kind.to_string(),
+    };
+    let title = format!("{} {}", package.name, package.version);
+
+    handle
+        .upgrade_in_event_loop(move |h| {
+            let data = h.global::<DependencyData>();
+            data.set_features(ModelRc::from(
+                Rc::new(VecModel::from(features)) as Rc<dyn Model<Data = DependencyFeature>>
+            ));
+            data.set_default_features(default_features);
+            data.set_features_parent(parent_package);
+            data.set_features_dep_kind(dep_kind.into());
+            data.set_features_title(title.into());
+            data.set_features_crate(crate_name);
+        })
+        .unwrap();
+    Ok(())
+}
+
+/// The entry as a table, a dependency only given by its version being turned into an inline table
+fn as_table_like(entry: &mut toml_edit::Item) -> anyhow::Result<&mut dyn toml_edit::TableLike> {
+    if let Some(version) = entry.as_value_mut().filter(|v| v.is_str()) {
+        let mut inline = toml_edit::InlineTable::new();
+        inline.insert("version", version.as_str().unwrap_or_default().into());
+        // keep the comment that follows the version
+        *inline.decor_mut() = version.decor().clone();
+        *version = toml_edit::Value::InlineTable(inline);
+    }
+    entry
+        .as_table_like_mut()
+        .context("Could not understand the manifest")
+}
+
+/// Write the requested features and `default-features` of the dependency in the manifest of
+/// `parent_package`. A dependency only given by its version is turned into an inline table.
+pub fn save(
+    metadata: &Metadata,
+    parent_package: &str,
+    crate_name: &str,
+    dep_kind: DependencyKind,
+    features: &[SharedString],
+    default_features: bool,
+) -> anyhow::Result<()> {
+    let (parent, dependency) = find_dependency(metadata, parent_package, crate_name, dep_kind)?;
+    let manifest = parent.manifest_path.as_std_path();
+    let target = dependency.target.as_ref().map(|t| t.to_string());
+    let key = dependency.rename.as_deref().unwrap_or(crate_name);
+    let mut edits = ManifestEdits::default();
+
+    // For an inherited dependency, they add up to the features of `[workspace.dependencies]`
+    let entry = edits.entry(manifest, dep_kind, target.as_deref(), key)?;
+    if !(features.is_empty() && entry.is_str()) {
+        let entry = as_table_like(entry)?;
+        if features.is_empty() {
+            entry.remove("features");
+        } else if let Some(array) = entry.get_mut("features").and_then(|f| f.as_array_mut()) {
+            // edit the existing list in place to keep its layout
+            let first_prefix = array.get(0).and_then(|f| f.decor().prefix().cloned());
+            array.retain(|f| f.as_str().is_some_and(|f| features.iter().any(|n| n == f)));
+            if let (Some(prefix), Some(first)) = (first_prefix, array.get_mut(0)) {
+                first.decor_mut().set_prefix(prefix);
+            }
+            for feature in features {
+                if !array.iter().any(|f| f.as_str() == Some(feature.as_str())) {
+                    array.push(feature.as_str());
+                }
+            }
+        } else {
+            entry.insert(
+                "features",
+                toml_edit::value(
+                    features
+                        .iter()
+                        .map(|f| f.as_str())
+                        .collect::<toml_edit::Array>(),
+                ),
+            );
+        }
+    }
+
+    // but the default features can only be disabled in `[workspace.dependencies]`
+    let entry = edits.dependency(
+        manifest,
+        metadata.workspace_root.as_std_path(),
+        dep_kind,
+        target.as_deref(),
+        key,
+    )?;
+    if !(default_features && entry.is_str()) {
+        let entry = as_table_like(entry)?;
+        let default_key = if entry.contains_key("default_features") {
+            "default_features"
+        } else {
+            "default-features"
+        };
+        if default_features {
+            entry.remove(default_key);
+        } else if entry.get(default_key).and_then(|v| v.as_bool()) != Some(false) {
+            entry.insert(default_key, toml_edit::value(false));
+        }
+    }
+
+    edits.write()?;
+    Ok(())
+}
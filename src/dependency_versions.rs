@@ -0,0 +1,189 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{CargoUI, DependencyData, DependencyVersion};
+use crate::dependency_features::{find_dependency, resolved_package};
+use anyhow::Context;
+use cargo_metadata::semver::{Version, VersionReq};
+use cargo_metadata::{DependencyKind, Metadata};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+use std::rc::Rc;
+
+/// Open the version picker with all the published versions of `crate_name`, the most recent
+/// first. The dependency is added to the selected package if `parent_package` is empty, and
+/// upgraded in `parent_package` otherwise.
+pub fn show(
+    metadata: &Metadata,
+    index: &crates_index::GitIndex,
+    parent_package: SharedString,
+    crate_name: SharedString,
+    dep_kind: DependencyKind,
+    handle: slint::Weak<CargoUI>,
+) -> anyhow::Result<()> {
+    let krate = index
+        .crate_(&crate_name)
+        .with_context(|| format!("'{}' is not in the crates index", crate_name))?;
+    // the locked version and the requirement of the dependency being upgraded
+    let current = if parent_package.is_empty() {
+        None
+    } else {
+        let (parent, dependency) =
+            find_dependency(metadata, &parent_package, &crate_name, dep_kind)?;
+        let package = resolved_package(metadata, parent, &crate_name, dep_kind)?;
+        Some((package.version.clone(), dependency.req.clone()))
+    };
+    let latest = krate
+        .highest_normal_version()
+        .unwrap_or(krate.highest_version())
+        .version()
+        .to_owned();
+
+    let mut versions: Vec<(Option<Version>, &crates_index::Version)> = krate
+        .versions()
+        .iter()
+        .map(|v| (Version::parse(v.version()).ok(), v))
+        .collect();
+    versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+    let versions: Vec<DependencyVersion> = versions
+        .into_iter()
+        .map(|(parsed, v)| {
+            let mut hints = Vec::new();
+            if v.version() == latest {
+                hints.push("latest".to_owned());
+            }
+            if let (Some((locked, req)), Some(parsed)) = (&current, &parsed) {
+                if parsed == locked {
+                    hints.push("current".to_owned());
+                } else if req.matches(parsed) {
+                    hints.push("compatible".to_owned());
+                }
+            }
+            // `2024-03-01T12:34:56Z`, only the date is shown
+            if let Some(date) = v.pubtime().and_then(|t| t.get(..10)) {
+                hints.push(date.to_owned());
+            }
+            DependencyVersion {
+                version: v.version().into(),
+                yanked: v.is_yanked(),
+                prerelease: parsed.as_ref().is_none_or(|p| !p.pre.is_empty()),
+                rust_version: v.rust_version().unwrap_or_default().into(),
+                hint: hints.join(", ").into(),
+            }
+        })
+        .collect();
+    let dep_kind = match dep_kind {
+        DependencyKind::Normal => String::new(),
+        kind => kind.to_string(),
+    };
+
+    handle
+        .upgrade_in_event_loop(move |h| {
+            let data = h.global::<DependencyData>();
+            data.set_versions(ModelRc::from(
+                Rc::new(VecModel::from(versions)) as Rc<dyn Model<Data = DependencyVersion>>
+            ));
+            data.set_requirement(latest.into());
+            data.set_versions_parent(parent_package);
+            data.set_versions_dep_kind(dep_kind.into());
+            data.set_versions_crate(crate_name);
+        })
+        .unwrap();
+    Ok(())
+}
+
+/// The most recent stable versions above `locked`: the highest one that satisfies the
+/// requirement, which `cargo update` can pick, and the highest one that needs a new requirement
+pub fn upgrades(
+    krate: &crates_index::Crate,
+    locked: &Version,
+    req: &VersionReq,
+) -> (Option<Version>, Option<Version>) {
+    let mut compatible: Option<Version> = None;
+    let mut incompatible: Option<Version> = None;
+    for v in krate.versions().iter().filter(|v| !v.is_yanked()) {
+        let Ok(version) = Version::parse(v.version()) else {
+            continue;
+        };
+        if !version.pre.is_empty() || version <= *locked {
+            continue;
+        }
+        let highest = if req.matches(&version) {
+            &mut compatible
+        } else {
+            &mut incompatible
+        };
+        if highest.as_ref().is_none_or(|h| *h < version) {
+            *highest = Some(version);
+        }
+    }
+    (compatible, incompatible)
+}
+
+/// The version that cargo would pick for the requirement among the versions in the picker, or an
+/// empty string if the requirement is invalid or matches no version
+pub fn selected_version(ui: &CargoUI, requirement: &str) -> SharedString {
+    let Ok(req) = VersionReq::parse(requirement) else {
+        return SharedString::default();
+    };
+    ui.global::<DependencyData>()
+        .get_versions()
+        .iter()
+        .filter(|v| !v.yanked)
+        .find(|v| Version::parse(&v.version).is_ok_and(|v| req.matches(&v)))
+        .map(|v| v.version)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn krate(versions: &[(&str, bool)]) -> crates_index::Crate {
+        let lines: Vec<String> = versions
+            .iter()
+            .map(|(version, yanked)| {
+                serde_json::json!({
+                    "name": "foo",
+                    "vers": version,
+                    "deps": [],
+                    "cksum": "0".repeat(64),
+                    "features": {},
+                    "yanked": yanked,
+                })
+                .to_string()
+            })
+            .collect();
+        crates_index::Crate::from_slice(lines.join("\n").as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn compatible_and_breaking_upgrades() {
+        let krate = krate(&[
+            ("1.0.0", false),
+            ("1.2.0", false),
+            ("1.3.0", true),
+            ("1.4.0-beta.1", false),
+            ("2.0.0", false),
+            ("2.1.0", false),
+            ("3.0.0-rc.1", false),
+        ]);
+        let locked = Version::parse("1.0.0").unwrap();
+        let req = VersionReq::parse("1.0").unwrap();
+        let version = |v: &str| Some(Version::parse(v).unwrap());
+        // the yanked versions and the pre-releases are skipped
+        assert_eq!(
+            upgrades(&krate, &locked, &req),
+            (version("1.2.0"), version("2.1.0"))
+        );
+
+        let locked = Version::parse("2.1.0").unwrap();
+        let req = VersionReq::parse("2").unwrap();
+        assert_eq!(upgrades(&krate, &locked, &req), (None, None));
+
+        // a requirement pinned to the locked version only allows breaking upgrades
+        let locked = Version::parse("1.0.0").unwrap();
+        let req = VersionReq::parse("=1.0.0").unwrap();
+        assert_eq!(upgrades(&krate, &locked, &req), (None, version("2.1.0")));
+    }
+}
@@ -0,0 +1,306 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{Diag, DiagFilter};
+use slint::SharedString;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Row of the model: a diagnostic, its group, and the header of the group it starts, if any
+#[derive(Clone)]
+struct Row {
+    index: usize,
+    key: SharedString,
+    group_header: SharedString,
+}
+
+/// What makes two diagnostics identical
+type DedupKey = (i32, SharedString, i32, i32, SharedString);
+
+/// The diagnostics of a build, in arrival order, shown with the filter and grouping chosen by the user.
+///
+/// Identical diagnostics (e.g. a warning emitted for both the lib and the test target) are only
+/// shown once.
+pub struct DiagnosticsModel {
+    diagnostics: RefCell<Vec<Diag>>,
+    /// For each diagnostic, the index of the first identical one if it is a duplicate
+    duplicate_of: RefCell<Vec<Option<usize>>>,
+    /// For each diagnostic, how many identical ones came after it
+    duplicates: RefCell<Vec<i32>>,
+    /// The index of the first diagnostic with each key
+    seen: RefCell<HashMap<DedupKey, usize>>,
+    filter: RefCell<DiagFilter>,
+    /// Sorted by group, then in arrival order
+    rows: RefCell<Vec<Row>>,
+    notify: slint::ModelNotify,
+}
+
+impl DiagnosticsModel {
+    pub fn new(filter: DiagFilter) -> Self {
+        Self {
+            diagnostics: Default::default(),
+            duplicate_of: Default::default(),
+            duplicates: Default::default(),
+            seen: Default::default(),
+            filter: filter.into(),
+            rows: Default::default(),
+            notify: Default::default(),
+        }
+    }
+
+    pub fn push(&self, diag: Diag) {
+        let index = self.diagnostics.borrow().len();
+        // only deduplicate diagnostics that have a location
+        let original = if diag.file.is_empty() {
+            None
+        } else {
+            let key = (
+                diag.level,
+                diag.file.clone(),
+                diag.line,
+                diag.column,
+                diag.short.clone(),
+            );
+            match self.seen.borrow_mut().entry(key) {
+                std::collections::hash_map::Entry::Occupied(e) => Some(*e.get()),
+                std::collections::hash_map::Entry::Vacant(e) => {
+                    e.insert(index);
+                    None
+                }
+            }
+        };
+        let accepted = self.accept(&diag);
+        let key = self.group_key(&diag);
+        self.diagnostics.borrow_mut().push(diag);
+        self.duplicate_of.borrow_mut().push(original);
+        self.duplicates.borrow_mut().push(0);
+
+        if let Some(original) = original {
+            self.duplicates.borrow_mut()[original] += 1;
+            let original_key = self.group_key(&self.diagnostics.borrow()[original]);
+            let row = self
+                .rows
+                .borrow()
+                .binary_search_by(|r| (&r.key, r.index).cmp(&(&original_key, original)));
+            if let Ok(row) = row {
+                self.notify.row_changed(row);
+            }
+        } else if accepted {
+            let mut rows = self.rows.borrow_mut();
+            // after the diagnostics of the same group
+            let row = rows.partition_point(|r| r.key <= key);
+            let group_header = if row > 0 && rows[row - 1].key == key {
+                SharedString::default()
+            } else {
+                key.clone()
+            };
+            rows.insert(
+                row,
+                Row {
+                    index,
+                    key,
+                    group_header,
+                },
+            );
+            drop(rows);
+            self.notify.row_added(row, 1);
+        }
+    }
+
+    pub fn set_filter(&self, filter: DiagFilter) {
+        *self.filter.borrow_mut() = filter;
+        self.relayout();
+    }
+
+    /// Call `f` on every diagnostic, including the hidden ones
+    pub fn update(&self, f: impl FnMut(&mut Diag)) {
+        self.diagnostics.borrow_mut().iter_mut().for_each(f);
+        self.notify.reset();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.borrow().is_empty()
+    }
+
+    /// Number of distinct diagnostics with the given level, regardless of the filter
+    pub fn level_count(&self, level: i32) -> usize {
+        self.diagnostics
+            .borrow()
+            .iter()
+            .zip(self.duplicate_of.borrow().iter())
+            .filter(|(d, original)| d.level == level && original.is_none())
+            .count()
+    }
+
+    fn group_key(&self, diag: &Diag) -> SharedString {
+        let (key, fallback) = match self.filter.borrow().group_by.as_str() {
+            "Crate" => (&diag.crate_name, "(no crate)"),
+            "File" => (&diag.file, "(no file)"),
+            "Lint" => (&diag.code, "(no code)"),
+            _ => return SharedString::default(),
+        };
+        if key.is_empty() {
+            fallback.into()
+        } else {
+            key.clone()
+        }
+    }
+
+    fn accept(&self, diag: &Diag) -> bool {
+        let filter = self.filter.borrow();
+        let level_shown = match diag.level {
+            1 => filter.errors,
+            2 => filter.warnings,
+            3 => filter.notes,
+            _ => filter.other,
+        };
+        let search = filter.search.trim().to_lowercase();
+        level_shown
+            && (search.is_empty()
+                || [&diag.short, &diag.file, &diag.code, &diag.crate_name]
+                    .iter()
+                    .any(|s| s.to_lowercase().contains(&search)))
+    }
+
+    /// Rebuild all the rows, when the filter changed
+    fn relayout(&self) {
+        let diagnostics = self.diagnostics.borrow();
+        let mut rows: Vec<Row> = diagnostics
+            .iter()
+            .zip(self.duplicate_of.borrow().iter())
+            .enumerate()
+            .filter(|(_, (d, original))| original.is_none() && self.accept(d))
+            .map(|(index, (d, _))| Row {
+                index,
+                key: self.group_key(d),
+                group_header: SharedString::default(),
+            })
+            .collect();
+        // stable sort, so the diagnostics of a group stay in arrival order
+        rows.sort_by(|a, b| a.key.cmp(&b.key));
+        for row in 0..rows.len() {
+            if row == 0 || rows[row - 1].key != rows[row].key {
+                rows[row].group_header = rows[row].key.clone();
+            }
+        }
+        drop(diagnostics);
+        self.rows.replace(rows);
+        self.notify.reset();
+    }
+}
+
+impl slint::Model for DiagnosticsModel {
+    type Data = Diag;
+
+    fn row_count(&self) -> usize {
+        self.rows.borrow().len()
+    }
+
+    fn row_data(&self, row: usize) -> Option<Self::Data> {
+        let row = self.rows.borrow().get(row)?.clone();
+        let mut diag = self.diagnostics.borrow().get(row.index)?.clone();
+        diag.group_header = row.group_header;
+        diag.duplicates = self.duplicates.borrow()[row.index];
+        Some(diag)
+    }
+
+    fn model_tracker(&self) -> &dyn slint::ModelTracker {
+        &self.notify
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slint::Model;
+
+    fn diag(level: i32, file: &str, line: i32, short: &str) -> Diag {
+        Diag {
+            level,
+            file: file.into(),
+            line,
+            short: short.into(),
+            ..Default::default()
+        }
+    }
+
+    fn filter(group_by: &str) -> DiagFilter {
+        DiagFilter {
+            errors: true,
+            warnings: true,
+            notes: true,
+            other: true,
+            group_by: group_by.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The rows as `header|file:line (duplicates)`
+    fn rows(model: &DiagnosticsModel) -> Vec<String> {
+        model
+            .iter()
+            .map(|d| {
+                format!(
+                    "{}|{}:{} ({})",
+                    d.group_header, d.file, d.line, d.duplicates
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn duplicates_are_counted_once() {
+        let model = DiagnosticsModel::new(filter("None"));
+        model.push(diag(2, "src/lib.rs", 1, "unused variable"));
+        model.push(diag(1, "src/lib.rs", 5, "mismatched types"));
+        // the same warning for the test target
+        model.push(diag(2, "src/lib.rs", 1, "unused variable"));
+        model.push(diag(2, "src/lib.rs", 1, "unused variable"));
+        // lines without a location are never merged
+        model.push(diag(0, "", 0, "Compiling"));
+        model.push(diag(0, "", 0, "Compiling"));
+        assert_eq!(
+            rows(&model),
+            [
+                "|src/lib.rs:1 (2)",
+                "|src/lib.rs:5 (0)",
+                "|:0 (0)",
+                "|:0 (0)"
+            ]
+        );
+        assert_eq!(model.level_count(2), 1);
+        assert_eq!(model.level_count(1), 1);
+    }
+
+    #[test]
+    fn grouped_in_arrival_order() {
+        let model = DiagnosticsModel::new(filter("File"));
+        model.push(diag(2, "src/main.rs", 3, "a"));
+        model.push(diag(2, "src/lib.rs", 7, "b"));
+        model.push(diag(2, "src/main.rs", 1, "c"));
+        model.push(diag(2, "src/lib.rs", 7, "b"));
+        model.push(diag(0, "", 0, "d"));
+        let expected = [
+            "(no file)|:0 (0)",
+            "src/lib.rs|src/lib.rs:7 (1)",
+            "src/main.rs|src/main.rs:3 (0)",
+            "|src/main.rs:1 (0)",
+        ];
+        assert_eq!(rows(&model), expected);
+
+        // the same rows when they are laid out at once
+        model.set_filter(filter("None"));
+        model.set_filter(filter("File"));
+        assert_eq!(rows(&model), expected);
+
+        let mut errors_only = filter("File");
+        errors_only.warnings = false;
+        model.set_filter(errors_only);
+        assert_eq!(rows(&model), ["(no file)|:0 (0)"]);
+    }
+}
@@ -0,0 +1,133 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use anyhow::Context;
+use std::path::Path;
+
+/// Open `file` at the given position.
+///
+/// `template` is a command line with `{file}`, `{line}` and `{col}` placeholders. Without it,
+/// `$VISUAL` or `$EDITOR` is used, and as a last resort the file is opened with the default
+/// application of the system. That is also the case for the editors that run in a terminal,
+/// since cargo-ui has none to give them.
+pub fn open_in_editor(
+    file: &Path,
+    line: i32,
+    column: i32,
+    template: Option<&str>,
+) -> anyhow::Result<()> {
+    let template = match template {
+        Some(template) => template.to_owned(),
+        None => match std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")) {
+            Ok(editor) if !is_terminal_editor(&editor) => default_template(&editor),
+            _ => {
+                return open::that_detached(file)
+                    .with_context(|| format!("Failed to open '{}'", file.display()))
+            }
+        },
+    };
+
+    let args = shlex::split(&template)
+        .with_context(|| format!("Could not parse the editor command '{}'", template))?;
+    let mut args = args.into_iter().map(|arg| {
+        arg.replace("{file}", &file.to_string_lossy())
+            .replace("{line}", &line.max(1).to_string())
+            .replace("{col}", &column.max(1).to_string())
+    });
+    let program = args.next().context("The editor command is empty")?;
+    let mut child = std::process::Command::new(&program)
+        .args(args)
+        .spawn()
+        .with_context(|| format!("Failed to launch '{}'", program))?;
+    // Wait for the editor in the background so that it does not stay a zombie once closed
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// The words of an editor command line
+///
+/// `$EDITOR` is often set to the bare path of the program, which may contain spaces.
+fn editor_words(editor: &str) -> Vec<String> {
+    if Path::new(editor).is_file() {
+        return vec![editor.to_owned()];
+    }
+    shlex::split(editor).unwrap_or_else(|| vec![editor.to_owned()])
+}
+
+/// The name of the program of an editor command line, without its directory
+fn editor_name(editor: &str) -> String {
+    Path::new(editor_words(editor).first().map_or("", String::as_str))
+        .file_stem()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Whether the editor needs a terminal to run in
+fn is_terminal_editor(editor: &str) -> bool {
+    matches!(
+        editor_name(editor).as_str(),
+        "vi" | "vim"
+            | "nvim"
+            | "nano"
+            | "pico"
+            | "hx"
+            | "helix"
+            | "micro"
+            | "kak"
+            | "joe"
+            | "ne"
+            | "mg"
+            | "ed"
+    ) || editor_words(editor).iter().any(|arg| arg == "-nw")
+}
+
+/// The command line template for the well known editors
+fn default_template(editor: &str) -> String {
+    let words = editor_words(editor);
+    let editor =
+        shlex::try_join(words.iter().map(String::as_str)).unwrap_or_else(|_| editor.to_owned());
+    match editor_name(editor.as_str()).as_str() {
+        "code" | "code-insiders" | "codium" => format!("{} -g {{file}}:{{line}}:{{col}}", editor),
+        "subl" | "zed" => format!("{} {{file}}:{{line}}:{{col}}", editor),
+        // gvim, emacs and most others understand `+line`
+        _ => format!("{} +{{line}} {{file}}", editor),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminal_editors_are_recognized() {
+        assert!(is_terminal_editor("vim"));
+        assert!(is_terminal_editor("/usr/bin/nano"));
+        assert!(is_terminal_editor("emacs -nw"));
+        assert!(!is_terminal_editor("emacs"));
+        assert!(!is_terminal_editor("gvim -f"));
+        assert!(!is_terminal_editor("code --wait"));
+        assert_eq!(
+            default_template("/usr/bin/code --wait"),
+            "/usr/bin/code --wait -g {file}:{line}:{col}"
+        );
+    }
+
+    #[test]
+    fn editor_path_with_spaces() {
+        let directory = tempfile::tempdir().unwrap();
+        let editor = directory.path().join("My Editors").join("code");
+        std::fs::create_dir(editor.parent().unwrap()).unwrap();
+        std::fs::write(&editor, "").unwrap();
+        let editor = editor.to_str().unwrap();
+        assert_eq!(editor_name(editor), "code");
+        let template = default_template(editor);
+        assert_eq!(
+            shlex::split(&template).unwrap(),
+            [editor, "-g", "{file}:{line}:{col}"]
+        );
+        // already quoted by the user
+        let quoted = shlex::try_quote(editor).unwrap();
+        assert_eq!(default_template(&quoted), template);
+    }
+}
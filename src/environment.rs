@@ -0,0 +1,65 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::EnvVar;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+/// Parse the environment variables of an `Action`: one `KEY=VALUE` per line
+pub fn parse_env(env: &str) -> Vec<(String, String)> {
+    env.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_owned(), value.to_owned()))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+/// Add a variable to the environment of an `Action`, unless it is already set
+pub fn add_var(env: &str, name: &str, value: &str) -> String {
+    if parse_env(env).iter().any(|(key, _)| key == name) {
+        return env.to_owned();
+    }
+    env.lines()
+        .filter(|line| !line.trim().is_empty())
+        .chain(std::iter::once(format!("{}={}", name, value).as_str()))
+        .join("\n")
+}
+
+/// The variables inherited from cargo-ui that are shown, as they change what cargo does
+fn is_relevant(name: &str) -> bool {
+    name.starts_with("CARGO") || name.starts_with("RUST")
+}
+
+/// The environment in which the command runs: the relevant variables of cargo-ui, and the ones
+/// set for the command
+pub fn effective(command: &std::process::Command) -> Vec<EnvVar> {
+    let mut vars: BTreeMap<String, EnvVar> = std::env::vars()
+        .filter(|(name, _)| is_relevant(name))
+        .map(|(name, value)| {
+            let var = EnvVar {
+                name: name.as_str().into(),
+                value: value.into(),
+                inherited: true,
+            };
+            (name, var)
+        })
+        .collect();
+    for (name, value) in command.get_envs() {
+        let name = name.to_string_lossy().into_owned();
+        match value {
+            Some(value) => {
+                let var = EnvVar {
+                    name: name.as_str().into(),
+                    value: value.to_string_lossy().as_ref().into(),
+                    inherited: false,
+                };
+                vars.insert(name, var);
+            }
+            None => {
+                vars.remove(&name);
+            }
+        }
+    }
+    vars.into_values().collect()
+}
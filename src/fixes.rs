@@ -0,0 +1,377 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use anyhow::Context;
+use cargo_metadata::diagnostic::{Applicability, Diagnostic, DiagnosticSpan};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A part of a file to replace, with byte offsets as reported by rustc
+#[derive(Debug, Clone, PartialEq)]
+struct Replacement {
+    file: PathBuf,
+    start: usize,
+    end: usize,
+    text: String,
+    /// The text that was replaced, as rustc saw it, to detect files that changed since the build
+    original: Option<String>,
+}
+
+impl Replacement {
+    fn overlaps(&self, other: &Replacement) -> bool {
+        self.file == other.file
+            && ((self.start < other.end && other.start < self.end) || self.start == other.start)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Fix {
+    replacements: Vec<Replacement>,
+    machine_applicable: bool,
+}
+
+/// The suggestions of the compiler for the diagnostics of the last build.
+///
+/// Offsets are those of the files when they were compiled, so the fixes that were already applied
+/// are remembered to shift the offsets of the next ones.
+#[derive(Debug, Default)]
+pub struct FixStore {
+    /// Indexed by the `id` of the `Diag`
+    fixes: Vec<Fix>,
+    applied: Vec<Replacement>,
+}
+
+/// Whether a diagnostic has a suggestion that can be applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fixable {
+    No,
+    /// The suggestion may be incorrect and needs to be reviewed
+    MaybeIncorrect,
+    MachineApplicable,
+}
+
+impl FixStore {
+    pub fn clear(&mut self) {
+        self.fixes.clear();
+        self.applied.clear();
+    }
+
+    /// Remember the suggestions of a diagnostic, and return the id to give to the `Diag`
+    pub fn add(&mut self, diagnostic: &Diagnostic) -> (i32, Fixable) {
+        // Several children may suggest alternatives for the same code, only take the first one
+        let fix = diagnostic
+            .children
+            .iter()
+            .chain(std::iter::once(diagnostic))
+            .find_map(|d| {
+                let spans: Vec<_> = d
+                    .spans
+                    .iter()
+                    .filter(|s| {
+                        s.suggested_replacement.is_some()
+                            && s.suggestion_applicability != Some(Applicability::HasPlaceholders)
+                    })
+                    .collect();
+                // The alternatives of one suggestion, like the paths of "consider importing one
+                // of these items", are spans at the same position: only the first one is kept
+                let mut replacements: Vec<Replacement> = Vec::new();
+                for s in &spans {
+                    let replacement = Replacement {
+                        file: s.file_name.clone().into(),
+                        start: s.byte_start as usize,
+                        end: s.byte_end as usize,
+                        text: s.suggested_replacement.clone().unwrap_or_default(),
+                        original: span_text(s),
+                    };
+                    if !replacements.iter().any(|r| r.overlaps(&replacement)) {
+                        replacements.push(replacement);
+                    }
+                }
+                (!spans.is_empty()).then(|| Fix {
+                    machine_applicable: spans.iter().all(|s| {
+                        s.suggestion_applicability == Some(Applicability::MachineApplicable)
+                    }),
+                    replacements,
+                })
+            })
+            .unwrap_or_default();
+        let fixable = if fix.replacements.is_empty() {
+            Fixable::No
+        } else if fix.machine_applicable {
+            Fixable::MachineApplicable
+        } else {
+            Fixable::MaybeIncorrect
+        };
+        self.fixes.push(fix);
+        ((self.fixes.len() - 1) as i32, fixable)
+    }
+
+    /// The ids of all the diagnostics with a machine applicable suggestion
+    pub fn machine_applicable(&self) -> Vec<i32> {
+        (0..self.fixes.len())
+            .filter(|i| {
+                self.fixes[*i].machine_applicable && !self.fixes[*i].replacements.is_empty()
+            })
+            .map(|i| i as i32)
+            .collect()
+    }
+
+    /// Apply the fixes with the given ids to the files, which are relative to `root`.
+    ///
+    /// Returns the ids of the fixes that were applied. Fixes that conflict with an already
+    /// applied fix are skipped. Nothing is written if a file changed since the build.
+    pub fn apply(&mut self, ids: &[i32], root: &Path) -> anyhow::Result<Vec<i32>> {
+        let mut contents: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+        // the replacements of this call, only recorded once they are written
+        let mut edits: Vec<Replacement> = Vec::new();
+        let mut done = Vec::new();
+        for id in ids {
+            let Some(fix) = self.fixes.get(*id as usize) else {
+                continue;
+            };
+            if fix.replacements.is_empty() {
+                continue;
+            }
+            let previous = || self.applied.iter().chain(edits.iter());
+            // The same diagnostic is emitted once per target: its fix is then already applied
+            if fix.replacements.iter().all(|r| previous().any(|a| a == r)) {
+                done.push(*id);
+                continue;
+            }
+            if fix
+                .replacements
+                .iter()
+                .any(|r| previous().any(|a| a.overlaps(r)))
+            {
+                continue;
+            }
+            let mut fix_edits = Vec::new();
+            for r in &fix.replacements {
+                let data = match contents.entry(r.file.clone()) {
+                    std::collections::hash_map::Entry::Occupied(e) => e.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(e) => {
+                        let path = root.join(&r.file);
+                        e.insert(
+                            std::fs::read(&path)
+                                .with_context(|| format!("Failed to load '{}'", path.display()))?,
+                        )
+                    }
+                };
+                let (start, end) = shifted(r, previous().chain(fix_edits.iter()));
+                let stale = end > data.len()
+                    || start > end
+                    || r.original
+                        .as_ref()
+                        .is_some_and(|o| !is_same_text(o, &data[start..end]));
+                if stale {
+                    anyhow::bail!("'{}' was modified since the build", r.file.display());
+                }
+                data.splice(start..end, r.text.bytes());
+                fix_edits.push(r.clone());
+            }
+            edits.extend(fix_edits);
+            done.push(*id);
+        }
+        for (file, data) in contents {
+            let path = root.join(&file);
+            std::fs::write(&path, data)
+                .with_context(|| format!("Failed to write '{}'", path.display()))?;
+            self.applied
+                .extend(edits.iter().filter(|e| e.file == file).cloned());
+        }
+        Ok(done)
+    }
+}
+
+/// The offsets of the replacement in the file where the `previous` replacements were made
+fn shifted<'a>(r: &Replacement, previous: impl Iterator<Item = &'a Replacement>) -> (usize, usize) {
+    let shift: isize = previous
+        .filter(|a| a.file == r.file && a.end <= r.start)
+        .map(|a| a.text.len() as isize - (a.end - a.start) as isize)
+        .sum();
+    (
+        (r.start as isize + shift) as usize,
+        (r.end as isize + shift) as usize,
+    )
+}
+
+/// Whether the text of a span in the file is the one that rustc quoted, which has `\n` line
+/// endings even in a file with `\r\n` ones
+fn is_same_text(original: &str, current: &[u8]) -> bool {
+    std::str::from_utf8(current).is_ok_and(|current| current.replace("\r\n", "\n") == original)
+}
+
+/// The text of the span when the file was compiled, from the lines quoted by rustc
+fn span_text(span: &DiagnosticSpan) -> Option<String> {
+    if span.text.is_empty() {
+        return None;
+    }
+    Some(
+        span.text
+            .iter()
+            .map(|line| {
+                line.text
+                    .chars()
+                    .skip(line.highlight_start.saturating_sub(1))
+                    .take(line.highlight_end.saturating_sub(line.highlight_start))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacement(start: usize, end: usize, original: &str, text: &str) -> Replacement {
+        Replacement {
+            file: "lib.rs".into(),
+            start,
+            end,
+            text: text.into(),
+            original: Some(original.into()),
+        }
+    }
+
+    fn store(fixes: Vec<Vec<Replacement>>) -> FixStore {
+        FixStore {
+            fixes: fixes
+                .into_iter()
+                .map(|replacements| Fix {
+                    replacements,
+                    machine_applicable: true,
+                })
+                .collect(),
+            applied: Vec::new(),
+        }
+    }
+
+    fn temp_root(source: &str) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("lib.rs"), source).unwrap();
+        root
+    }
+
+    #[test]
+    fn shift_by_previous_replacements() {
+        let previous = [
+            replacement(0, 2, "ab", "abcd"),
+            replacement(10, 14, "wxyz", ""),
+        ];
+        assert_eq!(
+            shifted(&replacement(5, 6, "f", "F"), previous.iter()),
+            (7, 8)
+        );
+        // replacements after it in the file do not move it
+        assert_eq!(
+            shifted(&replacement(20, 21, "u", "U"), previous.iter()),
+            (18, 19)
+        );
+    }
+
+    #[test]
+    fn two_fixes_on_the_same_line() {
+        let source = "let x = foo(a,b);\n";
+        let dir = temp_root(source);
+        let root = dir.path();
+        let mut fixes = store(vec![
+            vec![replacement(8, 11, "foo", "self.foo")],
+            vec![replacement(14, 15, "b", "&b")],
+        ]);
+        assert_eq!(fixes.apply(&[0, 1], root).unwrap(), vec![0, 1]);
+        assert_eq!(
+            std::fs::read_to_string(root.join("lib.rs")).unwrap(),
+            "let x = self.foo(a,&b);\n"
+        );
+        // applied in a later call, the offsets are still shifted
+        std::fs::write(root.join("lib.rs"), source).unwrap();
+        let mut fixes = store(vec![
+            vec![replacement(8, 11, "foo", "self.foo")],
+            vec![replacement(12, 13, "a", "&a")],
+        ]);
+        fixes.apply(&[0], root).unwrap();
+        fixes.apply(&[1], root).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("lib.rs")).unwrap(),
+            "let x = self.foo(&a,b);\n"
+        );
+        // applying a fix twice does nothing
+        assert_eq!(fixes.apply(&[0], root).unwrap(), vec![0]);
+        assert_eq!(
+            std::fs::read_to_string(root.join("lib.rs")).unwrap(),
+            "let x = self.foo(&a,b);\n"
+        );
+    }
+
+    #[test]
+    fn only_the_first_alternative_is_kept() {
+        let span = |text: &str| {
+            serde_json::json!({
+                "file_name": "lib.rs", "byte_start": 0, "byte_end": 0,
+                "line_start": 1, "line_end": 1, "column_start": 1, "column_end": 1,
+                "is_primary": true, "text": [], "label": null,
+                "suggested_replacement": text,
+                "suggestion_applicability": "MaybeIncorrect", "expansion": null
+            })
+        };
+        let diagnostic: Diagnostic = serde_json::from_value(serde_json::json!({
+            "message": "cannot find type `HashMap` in this scope",
+            "code": null, "level": "error", "spans": [], "rendered": null,
+            "children": [{
+                "message": "consider importing one of these items",
+                "code": null, "level": "help", "children": [], "rendered": null,
+                "spans": [
+                    span("use std::collections::HashMap;\n"),
+                    span("use hashbrown::HashMap;\n"),
+                ]
+            }]
+        }))
+        .unwrap();
+        let mut fixes = FixStore::default();
+        assert_eq!(fixes.add(&diagnostic), (0, Fixable::MaybeIncorrect));
+        let texts: Vec<&str> = fixes.fixes[0]
+            .replacements
+            .iter()
+            .map(|r| r.text.as_str())
+            .collect();
+        assert_eq!(texts, ["use std::collections::HashMap;\n"]);
+    }
+
+    #[test]
+    fn multi_line_fix_in_a_crlf_file() {
+        let dir = temp_root("fn f() {\r\n    g()\r\n}\r\n");
+        let root = dir.path();
+        let mut fixes = store(vec![vec![replacement(7, 20, "{\n    g()\n}", "{ g(); }")]]);
+        assert_eq!(fixes.apply(&[0], root).unwrap(), vec![0]);
+        assert_eq!(
+            std::fs::read_to_string(root.join("lib.rs")).unwrap(),
+            "fn f() { g(); }\r\n"
+        );
+    }
+
+    #[test]
+    fn partial_failure_records_nothing() {
+        let dir = temp_root("let x = foo(a,b);\n");
+        let root = dir.path();
+        let mut fixes = store(vec![
+            vec![replacement(8, 11, "foo", "self.foo")],
+            // the text at the span is not what rustc saw
+            vec![replacement(14, 15, "c", "&c")],
+        ]);
+        assert!(fixes.apply(&[0, 1], root).is_err());
+        assert!(fixes.applied.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(root.join("lib.rs")).unwrap(),
+            "let x = foo(a,b);\n"
+        );
+        // the offsets of the next fixes are not shifted by the failed ones
+        assert_eq!(fixes.apply(&[0], root).unwrap(), vec![0]);
+        assert_eq!(
+            std::fs::read_to_string(root.join("lib.rs")).unwrap(),
+            "let x = self.foo(a,b);\n"
+        );
+    }
+}
@@ -0,0 +1,476 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{Action, CargoUI, Diag, HistoryData, HistoryEntry, HistoryLine, RunConfiguration};
+use crate::cargo::{CargoMessage, FeatureSettings};
+use anyhow::Context;
+use serde_json::{json, Value};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Number of runs that are kept, the older ones are deleted
+const MAX_RUNS: usize = 100;
+
+/// One invocation of cargo, as stored in `target/cargo-ui/history/<timestamp>.json`
+pub struct Run {
+    /// The action and the features that were selected
+    pub configuration: RunConfiguration,
+    pub command_line: String,
+    pub invocation: Invocation,
+    /// milliseconds since the epoch
+    pub timestamp: u64,
+    /// None if the process was killed by a signal
+    pub exit_code: Option<i32>,
+    /// The user cancelled the run
+    pub cancelled: bool,
+    pub duration: std::time::Duration,
+    pub diagnostics: Vec<Diag>,
+}
+
+/// The process that was started, to run exactly the same command again even if the settings
+/// of the workspace changed since
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Invocation {
+    pub program: String,
+    pub args: Vec<String>,
+    /// The variables that were set, or removed if `None`
+    pub env: Vec<(String, Option<String>)>,
+    pub current_dir: Option<String>,
+}
+
+impl Invocation {
+    pub fn of(command: &std::process::Command) -> Self {
+        let string = |s: &std::ffi::OsStr| s.to_string_lossy().into_owned();
+        Self {
+            program: string(command.get_program()),
+            args: command.get_args().map(string).collect(),
+            env: command
+                .get_envs()
+                .map(|(name, value)| (string(name), value.map(string)))
+                .collect(),
+            current_dir: command
+                .get_current_dir()
+                .map(|d| d.to_string_lossy().into_owned()),
+        }
+    }
+
+    pub fn command(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.program);
+        command.args(&self.args);
+        for (name, value) in &self.env {
+            match value {
+                Some(value) => command.env(name, value),
+                None => command.env_remove(name),
+            };
+        }
+        if let Some(directory) = &self.current_dir {
+            command.current_dir(directory);
+        }
+        command
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "program": self.program,
+            "args": self.args,
+            "env": self.env,
+            "current-dir": self.current_dir,
+        })
+    }
+
+    fn from_json(value: &Value) -> Self {
+        let array = |key: &str| value[key].as_array().map_or(&[][..], |a| a.as_slice());
+        Self {
+            program: value["program"].as_str().unwrap_or_default().to_owned(),
+            args: array("args")
+                .iter()
+                .filter_map(|a| Some(a.as_str()?.to_owned()))
+                .collect(),
+            env: array("env")
+                .iter()
+                .filter_map(|var| {
+                    let name = var[0].as_str()?.to_owned();
+                    Some((name, var[1].as_str().map(str::to_owned)))
+                })
+                .collect(),
+            current_dir: value["current-dir"].as_str().map(str::to_owned),
+        }
+    }
+}
+
+/// The command line as it could be typed in a shell
+pub fn command_line(command: &std::process::Command) -> String {
+    let program = command.get_program().to_string_lossy().into_owned();
+    std::iter::once(program)
+        .chain(command.get_args().map(|a| a.to_string_lossy().into_owned()))
+        .map(|a| shlex::try_quote(&a).map_or(a.clone(), |q| q.into_owned()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+fn diag_to_json(diag: &Diag) -> Value {
+    json!({
+        "level": diag.level,
+        "short": diag.short.as_str(),
+        "expanded": diag.expanded.as_str(),
+        "file": diag.file.as_str(),
+        "line": diag.line,
+        "column": diag.column,
+        "code": diag.code.as_str(),
+        "crate": diag.crate_name.as_str(),
+    })
+}
+
+fn diag_from_json(value: &Value) -> Diag {
+    let str = |key: &str| SharedString::from(value[key].as_str().unwrap_or_default());
+    let int = |key: &str| value[key].as_i64().unwrap_or_default() as i32;
+    Diag {
+        level: int("level"),
+        short: str("short"),
+        expanded: str("expanded"),
+        file: str("file"),
+        line: int("line"),
+        column: int("column"),
+        code: str("code"),
+        crate_name: str("crate"),
+        ..Default::default()
+    }
+}
+
+impl Run {
+    fn to_json(&self) -> Value {
+        let action = &self.configuration.action;
+        json!({
+            "command-line": self.command_line,
+            "invocation": self.invocation.to_json(),
+            "timestamp": self.timestamp,
+            "exit-code": self.exit_code,
+            "cancelled": self.cancelled,
+            "duration": self.duration.as_secs_f64(),
+            "action": {
+                "command": action.command.as_str(),
+                "package": action.package.as_str(),
+                "profile": action.profile.as_str(),
+                "toolchain": action.toolchain.as_str(),
+                "extra": action.extra.as_str(),
+                "target-triple": action.target_triple.as_str(),
+                "arguments": action.arguments.as_str(),
+                "env": action.env.as_str(),
+                "working-directory": action.working_directory.as_str(),
+            },
+            "features": self.configuration.features.as_str(),
+            "default-features": self.configuration.default_features,
+            "diagnostics": self.diagnostics.iter().map(diag_to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    fn from_json(value: &Value) -> Self {
+        let action = &value["action"];
+        let str = |key: &str| SharedString::from(action[key].as_str().unwrap_or_default());
+        Self {
+            configuration: RunConfiguration {
+                name: value["command-line"].as_str().unwrap_or_default().into(),
+                action: Action {
+                    command: str("command"),
+                    package: str("package"),
+                    profile: str("profile"),
+                    toolchain: str("toolchain"),
+                    extra: str("extra"),
+                    target_triple: str("target-triple"),
+                    arguments: str("arguments"),
+                    env: str("env"),
+                    working_directory: str("working-directory"),
+                },
+                features: value["features"].as_str().unwrap_or_default().into(),
+                default_features: value["default-features"].as_bool().unwrap_or(true),
+            },
+            command_line: value["command-line"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned(),
+            invocation: Invocation::from_json(&value["invocation"]),
+            timestamp: value["timestamp"].as_u64().unwrap_or_default(),
+            exit_code: value["exit-code"].as_i64().map(|c| c as i32),
+            cancelled: value["cancelled"].as_bool().unwrap_or_default(),
+            duration: std::time::Duration::from_secs_f64(
+                value["duration"].as_f64().unwrap_or_default().max(0.),
+            ),
+            diagnostics: value["diagnostics"]
+                .as_array()
+                .map(|d| d.iter().map(diag_from_json).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to load '{}'", path.display()))?;
+        let value: Value = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        Ok(Self::from_json(&value))
+    }
+
+    fn level_count(&self, level: i32) -> i32 {
+        self.diagnostics.iter().filter(|d| d.level == level).count() as i32
+    }
+
+    fn to_entry(&self, path: &Path) -> HistoryEntry {
+        HistoryEntry {
+            path: path.to_string_lossy().as_ref().into(),
+            configuration: self.configuration.clone(),
+            time: format_timestamp(self.timestamp).into(),
+            status: match self.exit_code {
+                _ if self.cancelled => "cancelled".into(),
+                Some(code) => format!("exit code {}", code),
+                None => "killed".into(),
+            }
+            .into(),
+            success: self.exit_code == Some(0),
+            duration: format!("{:.1}s", self.duration.as_secs_f64()).into(),
+            errors: self.level_count(1),
+            warnings: self.level_count(2),
+            selected: false,
+        }
+    }
+}
+
+/// `2024-03-01 12:34:56 UTC`
+fn format_timestamp(timestamp: u64) -> String {
+    let seconds = timestamp / 1000;
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Next to the results of the benchmarks, out of the sources so that the tree stays clean
+fn history_directory(target_directory: &Path) -> PathBuf {
+    target_directory.join("cargo-ui").join("history")
+}
+
+/// The files of the stored runs, the most recent first
+fn run_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|e| Some(e.ok()?.path()))
+                .filter(|p| p.extension().is_some_and(|e| e == "json"))
+                .collect()
+        })
+        .unwrap_or_default();
+    // the names are timestamps of the same length
+    files.sort();
+    files.reverse();
+    files
+}
+
+/// Store a run, and forget the oldest ones. Returns the path of the stored run
+pub fn record(run: &Run, target_directory: &Path) -> anyhow::Result<PathBuf> {
+    let directory = history_directory(target_directory);
+    std::fs::create_dir_all(&directory)
+        .with_context(|| format!("Failed to create '{}'", directory.display()))?;
+    let path = directory.join(format!("{:016}.json", run.timestamp));
+    std::fs::write(&path, serde_json::to_string(&run.to_json())?)
+        .with_context(|| format!("Failed to write '{}'", path.display()))?;
+    for old in run_files(&directory).iter().skip(MAX_RUNS) {
+        std::fs::remove_file(old).ok();
+    }
+    Ok(path)
+}
+
+/// Show the list of the stored runs in the History tab
+pub fn load(target_directory: &Path, handle: slint::Weak<CargoUI>) {
+    let entries: Vec<HistoryEntry> = run_files(&history_directory(target_directory))
+        .iter()
+        .filter_map(|path| Some(Run::load(path).ok()?.to_entry(path)))
+        .collect();
+    handle
+        .upgrade_in_event_loop(move |h| {
+            h.global::<HistoryData>().set_entries(ModelRc::from(
+                Rc::new(VecModel::from(entries)) as Rc<dyn Model<Data = HistoryEntry>>
+            ));
+        })
+        .unwrap();
+}
+
+/// Show a run that was just recorded at the top of the History tab
+pub fn prepend(run: &Run, path: &Path, handle: slint::Weak<CargoUI>) {
+    let entry = run.to_entry(path);
+    handle
+        .upgrade_in_event_loop(move |h| {
+            let data = h.global::<HistoryData>();
+            let entries = data.get_entries();
+            match entries.as_any().downcast_ref::<VecModel<HistoryEntry>>() {
+                Some(model) => {
+                    model.insert(0, entry);
+                    while model.row_count() > MAX_RUNS {
+                        model.remove(model.row_count() - 1);
+                    }
+                }
+                None => data.set_entries(ModelRc::from(
+                    Rc::new(VecModel::from(vec![entry])) as Rc<dyn Model<Data = HistoryEntry>>
+                )),
+            }
+        })
+        .unwrap();
+}
+
+/// The message that runs a stored run again, with the same command line and environment
+pub fn rerun_message(path: &str) -> anyhow::Result<CargoMessage> {
+    let run = Run::load(Path::new(path))?;
+    Ok(CargoMessage::Action {
+        feature_settings: FeatureSettings::from_configuration(&run.configuration),
+        action: run.configuration.action,
+        invocation: Some(run.invocation),
+    })
+}
+
+fn diag_line(diag: &Diag, change: i32) -> HistoryLine {
+    let mut text = String::new();
+    if !diag.code.is_empty() {
+        text += &format!("[{}] ", diag.code);
+    }
+    text += &diag.short;
+    if !diag.file.is_empty() {
+        text += &format!("  ({}:{}:{})", diag.file, diag.line, diag.column);
+    }
+    HistoryLine {
+        text: text.into(),
+        level: diag.level,
+        change,
+    }
+}
+
+fn set_details(ui: &CargoUI, title: String, lines: Vec<HistoryLine>) {
+    let data = ui.global::<HistoryData>();
+    data.set_details_title(title.into());
+    data.set_details(ModelRc::from(
+        Rc::new(VecModel::from(lines)) as Rc<dyn Model<Data = HistoryLine>>
+    ));
+}
+
+/// Show the diagnostics of a run
+pub fn show(ui: &CargoUI, path: &str) {
+    match Run::load(Path::new(path)) {
+        Ok(run) => set_details(
+            ui,
+            format!("{}\n{}", format_timestamp(run.timestamp), run.command_line),
+            run.diagnostics.iter().map(|d| diag_line(d, 0)).collect(),
+        ),
+        Err(e) => set_details(ui, format!("{:#}", e), Vec::new()),
+    }
+}
+
+/// Show the diagnostics that appeared or disappeared between the two selected runs
+pub fn compare_selected(ui: &CargoUI) {
+    let selected: Vec<HistoryEntry> = ui
+        .global::<HistoryData>()
+        .get_entries()
+        .iter()
+        .filter(|e| e.selected)
+        .collect();
+    // The entries are sorted from the most recent one
+    let [newer, older] = selected.as_slice() else {
+        set_details(ui, "Select two runs to compare them".into(), Vec::new());
+        return;
+    };
+    let (older, newer) = match (
+        Run::load(Path::new(older.path.as_str())),
+        Run::load(Path::new(newer.path.as_str())),
+    ) {
+        (Ok(older), Ok(newer)) => (older, newer),
+        (Err(e), _) | (_, Err(e)) => {
+            set_details(ui, format!("{:#}", e), Vec::new());
+            return;
+        }
+    };
+
+    // The lines move when the code is edited, so they are not part of the comparison
+    let key = |d: &Diag| (d.level, d.code.clone(), d.file.clone(), d.short.clone());
+    let older_keys: HashSet<_> = older.diagnostics.iter().map(key).collect();
+    let newer_keys: HashSet<_> = newer.diagnostics.iter().map(key).collect();
+    let added: Vec<HistoryLine> = newer
+        .diagnostics
+        .iter()
+        .filter(|d| !older_keys.contains(&key(d)))
+        .map(|d| diag_line(d, 2))
+        .collect();
+    let fixed: Vec<HistoryLine> = older
+        .diagnostics
+        .iter()
+        .filter(|d| !newer_keys.contains(&key(d)))
+        .map(|d| diag_line(d, 1))
+        .collect();
+    let unchanged = newer.diagnostics.len() - added.len();
+
+    let mut title = format!(
+        "{} → {}: {} new, {} fixed, {} unchanged",
+        format_timestamp(older.timestamp),
+        format_timestamp(newer.timestamp),
+        added.len(),
+        fixed.len(),
+        unchanged
+    );
+    if older.command_line != newer.command_line {
+        title += &format!("\n- {}\n+ {}", older.command_line, newer.command_line);
+    }
+    set_details(ui, title, added.into_iter().chain(fixed).collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invocation_is_stored_exactly() {
+        let mut command = std::process::Command::new("cargo");
+        command
+            .args(["run", "--", "a b", "--flag"])
+            .env("RUST_LOG", "debug")
+            .env_remove("CARGO_TARGET_DIR")
+            .current_dir("/tmp");
+        let invocation = Invocation::of(&command);
+        let stored = Invocation::from_json(&invocation.to_json());
+        assert_eq!(stored, invocation);
+
+        let rerun = stored.command();
+        let rerun = rerun.as_std();
+        assert_eq!(rerun.get_program(), "cargo");
+        assert!(rerun.get_args().eq(["run", "--", "a b", "--flag"]));
+        let mut envs: Vec<_> = rerun.get_envs().collect();
+        envs.sort();
+        assert_eq!(
+            envs,
+            [
+                ("CARGO_TARGET_DIR".as_ref(), None),
+                ("RUST_LOG".as_ref(), Some("debug".as_ref()))
+            ]
+        );
+        assert_eq!(rerun.get_current_dir(), Some(Path::new("/tmp")));
+    }
+}
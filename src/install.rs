@@ -31,7 +31,7 @@ impl InstallJob {
 pub async fn refresh_install_list(
     handle: slint::Weak<CargoUI>,
 ) -> tokio::io::Result<Vec<InstalledCrate>> {
-    let mut cargo_install_command = cargo::cargo_command();
+    let mut cargo_install_command = cargo::cargo_command("");
     cargo_install_command.arg("install").arg("--list");
     let mut spawn_result = cargo_install_command
         .stdout(std::process::Stdio::piped())
@@ -90,8 +90,12 @@ fn report_error<T: Default>(_handle: slint::Weak<CargoUI>, arg: &str) -> T {
     Default::default()
 }
 
-pub async fn process_install(job: InstallJob, handle: slint::Weak<CargoUI>) -> std::io::Result<()> {
-    let mut cmd = cargo::cargo_command();
+pub async fn process_install(
+    job: InstallJob,
+    toolchain: SharedString,
+    handle: slint::Weak<CargoUI>,
+) -> std::io::Result<()> {
+    let mut cmd = cargo::cargo_command(&toolchain);
     match &job {
         InstallJob::Install(cr) => cmd.arg("install").arg("--force").arg(cr.as_str()),
         InstallJob::Uninstall(cr) => cmd.arg("uninstall").arg(cr.as_str()),
@@ -171,8 +175,11 @@ pub fn apply_install_list(
         })
     }
 
+    let nextest_available = list.iter().any(|cr| cr.name == "cargo-nextest");
     handle
         .upgrade_in_event_loop(move |ui| {
+            ui.global::<TestData>()
+                .set_nextest_available(nextest_available);
             ui.global::<CargoInstallData>().set_crates(ModelRc::from(
                 Rc::new(VecModel::from(list)) as Rc<dyn Model<Data = InstalledCrate>>,
             ));
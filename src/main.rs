@@ -12,14 +12,33 @@ mod generated_code {
 use cargo_metadata::DependencyKind;
 pub use generated_code::*;
 
+mod bench;
+mod bulk_upgrade;
 mod cargo;
+mod console;
+mod dependency_features;
+mod dependency_versions;
+mod diagnostics;
+mod editor;
+mod environment;
+mod fixes;
+mod history;
 mod install;
+mod manifest_edits;
+mod process;
+mod profiles;
 mod rustup;
+mod settings;
+mod targets;
+mod test_runner;
+mod toolchain_file;
+mod watch;
 
 use install::InstallJob;
 use slint::Model;
 
 use crate::cargo::{CargoMessage, FeatureSettings};
+use crate::rustup::RustupMessage;
 
 fn main() {
     let cargo_ui = CargoUI::new().unwrap();
@@ -42,6 +61,7 @@ fn main() {
                 .send(CargoMessage::Action {
                     action,
                     feature_settings: FeatureSettings::new(&ui_handle.upgrade().unwrap()),
+                    invocation: None,
                 })
                 .unwrap()
         }
@@ -50,6 +70,14 @@ fn main() {
         let cargo_channel = cargo_worker.channel.clone();
         move || cargo_channel.send(CargoMessage::Cancel).unwrap()
     });
+    cargo_ui.on_watch_changed({
+        let cargo_channel = cargo_worker.channel.clone();
+        let ui_handle = cargo_ui.as_weak();
+        move || {
+            let watch = ui_handle.upgrade().unwrap().get_watch();
+            cargo_channel.send(CargoMessage::SetWatch(watch)).unwrap()
+        }
+    });
     cargo_ui.on_show_open_dialog({
         let cargo_channel = cargo_worker.channel.clone();
         move || cargo_channel.send(CargoMessage::ShowOpenDialog).unwrap()
@@ -67,6 +95,191 @@ fn main() {
         }
     });
 
+    cargo_ui.on_toolchain_selected({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |toolchain| {
+            cargo_channel
+                .send(CargoMessage::ToolchainSelected(toolchain))
+                .unwrap()
+        }
+    });
+
+    cargo_ui.on_apply_fix({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |id| cargo_channel.send(CargoMessage::ApplyFix(id)).unwrap()
+    });
+    cargo_ui.on_apply_all_fixes({
+        let cargo_channel = cargo_worker.channel.clone();
+        move || cargo_channel.send(CargoMessage::ApplyAllFixes).unwrap()
+    });
+    cargo_ui.on_diagnostics_filter_changed({
+        let ui_handle = cargo_ui.as_weak();
+        move || {
+            let ui = ui_handle.upgrade().unwrap();
+            let model_handle = ui.get_diagnostics();
+            if let Some(model) = model_handle
+                .as_any()
+                .downcast_ref::<diagnostics::DiagnosticsModel>()
+            {
+                model.set_filter(ui.get_diagnostics_filter());
+            }
+        }
+    });
+    cargo_ui.on_console_input({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |text| {
+            cargo_channel
+                .send(CargoMessage::ConsoleInput(text))
+                .unwrap()
+        }
+    });
+    cargo_ui.on_close_console_input({
+        let cargo_channel = cargo_worker.channel.clone();
+        move || cargo_channel.send(CargoMessage::CloseConsoleInput).unwrap()
+    });
+    cargo_ui.on_open_in_editor({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |file, line, column| {
+            cargo_channel
+                .send(CargoMessage::OpenInEditor { file, line, column })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<ToolchainFileData>().on_save({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |toolchain_override| {
+            cargo_channel
+                .send(CargoMessage::SaveToolchainFile(toolchain_override))
+                .unwrap()
+        }
+    });
+
+    let test_data = cargo_ui.global::<TestData>();
+    test_data.on_refresh({
+        let cargo_channel = cargo_worker.channel.clone();
+        let ui_handle = cargo_ui.as_weak();
+        move || {
+            cargo_channel
+                .send(CargoMessage::ListTests {
+                    feature_settings: FeatureSettings::new(&ui_handle.upgrade().unwrap()),
+                    runner: test_runner::TestRunner::new(&ui_handle.upgrade().unwrap()),
+                })
+                .unwrap()
+        }
+    });
+    test_data.on_runner_changed({
+        let cargo_channel = cargo_worker.channel.clone();
+        let ui_handle = cargo_ui.as_weak();
+        move || {
+            cargo_channel
+                .send(CargoMessage::TestRunnerChanged(
+                    test_runner::TestRunner::new(&ui_handle.upgrade().unwrap()),
+                ))
+                .unwrap()
+        }
+    });
+    test_data.on_run_all({
+        let ui_handle = cargo_ui.as_weak();
+        move || test_runner::run_tests(&ui_handle.upgrade().unwrap(), |_| true)
+    });
+    test_data.on_run_selected({
+        let ui_handle = cargo_ui.as_weak();
+        move || test_runner::run_tests(&ui_handle.upgrade().unwrap(), |t| t.selected)
+    });
+    test_data.on_run_failed({
+        let ui_handle = cargo_ui.as_weak();
+        move || {
+            test_runner::run_tests(&ui_handle.upgrade().unwrap(), |t| {
+                t.status == test_runner::TestStatus::Failed as i32
+            })
+        }
+    });
+    test_data.on_run_one({
+        let ui_handle = cargo_ui.as_weak();
+        move |target, name| {
+            test_runner::run_tests(&ui_handle.upgrade().unwrap(), |t| {
+                t.target == target && t.name == name
+            })
+        }
+    });
+
+    cargo_ui.global::<ProfileData>().on_save({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |profile| {
+            cargo_channel
+                .send(CargoMessage::SaveProfile(profile))
+                .unwrap()
+        }
+    });
+
+    let history_data = cargo_ui.global::<HistoryData>();
+    history_data.on_show({
+        let ui_handle = cargo_ui.as_weak();
+        move |path| history::show(&ui_handle.upgrade().unwrap(), &path)
+    });
+    history_data.on_compare({
+        let ui_handle = cargo_ui.as_weak();
+        move || history::compare_selected(&ui_handle.upgrade().unwrap())
+    });
+    history_data.on_rerun({
+        let cargo_channel = cargo_worker.channel.clone();
+        let ui_handle = cargo_ui.as_weak();
+        move |path| {
+            let ui = ui_handle.upgrade().unwrap();
+            match history::rerun_message(&path) {
+                Ok(message) => {
+                    ui.set_build_pane_visible(true);
+                    cargo_channel.send(message).unwrap()
+                }
+                Err(e) => ui.set_status(format!("{:#}", e).into()),
+            }
+        }
+    });
+
+    let run_configuration_data = cargo_ui.global::<RunConfigurationData>();
+    run_configuration_data.on_run({
+        let cargo_channel = cargo_worker.channel.clone();
+        let ui_handle = cargo_ui.as_weak();
+        move |name| {
+            let ui = ui_handle.upgrade().unwrap();
+            let Some(configuration) = ui
+                .global::<RunConfigurationData>()
+                .get_configurations()
+                .iter()
+                .find(|c| c.name == name)
+            else {
+                return;
+            };
+            cargo_channel
+                .send(CargoMessage::Action {
+                    feature_settings: FeatureSettings::from_configuration(&configuration),
+                    action: configuration.action,
+                    invocation: None,
+                })
+                .unwrap()
+        }
+    });
+    run_configuration_data.on_save({
+        let cargo_channel = cargo_worker.channel.clone();
+        let ui_handle = cargo_ui.as_weak();
+        move |mut configuration| {
+            FeatureSettings::new(&ui_handle.upgrade().unwrap()).store(&mut configuration);
+            cargo_channel
+                .send(CargoMessage::SaveRunConfiguration(configuration))
+                .unwrap()
+        }
+    });
+    run_configuration_data
+        .on_add_env_var(|env, name, value| environment::add_var(&env, &name, &value).into());
+    run_configuration_data.on_remove({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |name| {
+            cargo_channel
+                .send(CargoMessage::RemoveRunConfiguration(name))
+                .unwrap()
+        }
+    });
+
     cargo_ui.global::<DependencyData>().on_remove({
         let cargo_channel = cargo_worker.channel.clone();
         move |pkg, dep, dep_kind| {
@@ -82,26 +295,160 @@ fn main() {
     cargo_ui.global::<DependencyData>().on_request_upgrade({
         let cargo_channel = cargo_worker.channel.clone();
         move |pkg, dep, dep_kind| {
+            cargo_channel
+                .send(CargoMessage::DependencyVersions {
+                    parent_package: pkg,
+                    crate_name: dep,
+                    dep_kind: dep_kind_from_str(dep_kind),
+                })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<DependencyData>().on_edit_features({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |pkg, dep, dep_kind| {
+            cargo_channel
+                .send(CargoMessage::DependencyFeatures {
+                    parent_package: pkg,
+                    crate_name: dep,
+                    dep_kind: dep_kind_from_str(dep_kind),
+                })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<DependencyData>().on_save_features({
+        let cargo_channel = cargo_worker.channel.clone();
+        let ui_handle = cargo_ui.as_weak();
+        move || {
+            let ui = ui_handle.upgrade().unwrap();
+            let data = ui.global::<DependencyData>();
+            cargo_channel
+                .send(CargoMessage::DependencySetFeatures {
+                    parent_package: data.get_features_parent(),
+                    crate_name: data.get_features_crate(),
+                    dep_kind: dep_kind_from_str(data.get_features_dep_kind()),
+                    features: data
+                        .get_features()
+                        .iter()
+                        .filter(|f| f.requested)
+                        .map(|f| f.name)
+                        .collect(),
+                    default_features: data.get_default_features(),
+                })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<DependencyData>().on_update_lockfile({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |dep, version, precise| {
+            cargo_channel
+                .send(CargoMessage::DependencyUpdateLockfile {
+                    crate_name: dep,
+                    version,
+                    precise,
+                })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<DependencyData>().on_bump_requirement({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |pkg, dep, dep_kind, version| {
             cargo_channel
                 .send(CargoMessage::DependencyUpgrade {
                     parent_package: pkg,
                     crate_name: dep,
                     dep_kind: dep_kind_from_str(dep_kind),
+                    version,
+                })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<DependencyData>().on_promote({
+        let cargo_channel = cargo_worker.channel.clone();
+        move |dep| {
+            cargo_channel
+                .send(CargoMessage::DependencyPromote(dep))
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<BulkUpgradeData>().on_open({
+        let cargo_channel = cargo_worker.channel.clone();
+        move || cargo_channel.send(CargoMessage::BulkUpgradeList).unwrap()
+    });
+    cargo_ui.global::<BulkUpgradeData>().on_apply({
+        let cargo_channel = cargo_worker.channel.clone();
+        let ui_handle = cargo_ui.as_weak();
+        move || {
+            let ui = ui_handle.upgrade().unwrap();
+            let data = ui.global::<BulkUpgradeData>();
+            let allow_breaking = data.get_allow_breaking();
+            let candidates = data
+                .get_candidates()
+                .iter()
+                .filter(|c| c.selected && !bulk_upgrade::new_version(c, allow_breaking).is_empty())
+                .collect();
+            cargo_channel
+                .send(CargoMessage::BulkUpgrade {
+                    candidates,
+                    allow_breaking,
                 })
                 .unwrap()
         }
     });
+    cargo_ui.global::<BulkUpgradeData>().on_rollback({
+        let cargo_channel = cargo_worker.channel.clone();
+        move || {
+            cargo_channel
+                .send(CargoMessage::BulkUpgradeRollback)
+                .unwrap()
+        }
+    });
     cargo_ui.global::<DependencyData>().on_add_dependency({
         let cargo_channel = cargo_worker.channel.clone();
         move |dep, dep_kind| {
             cargo_channel
-                .send(CargoMessage::DependencyAdd {
+                .send(CargoMessage::DependencyVersions {
+                    parent_package: Default::default(),
                     crate_name: dep,
                     dep_kind: dep_kind_from_str(dep_kind),
                 })
                 .unwrap()
         }
     });
+    cargo_ui.global::<DependencyData>().on_apply_version({
+        let cargo_channel = cargo_worker.channel.clone();
+        let ui_handle = cargo_ui.as_weak();
+        move || {
+            let ui = ui_handle.upgrade().unwrap();
+            let data = ui.global::<DependencyData>();
+            let parent_package = data.get_versions_parent();
+            let crate_name = data.get_versions_crate();
+            let dep_kind = dep_kind_from_str(data.get_versions_dep_kind());
+            let version = data.get_requirement();
+            cargo_channel
+                .send(if parent_package.is_empty() {
+                    CargoMessage::DependencyAdd {
+                        crate_name,
+                        dep_kind,
+                        version,
+                    }
+                } else {
+                    CargoMessage::DependencyUpgrade {
+                        parent_package,
+                        crate_name,
+                        dep_kind,
+                        version,
+                    }
+                })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<DependencyData>().on_selected_version({
+        let ui_handle = cargo_ui.as_weak();
+        move |requirement| {
+            dependency_versions::selected_version(&ui_handle.upgrade().unwrap(), &requirement)
+        }
+    });
     cargo_ui.global::<CargoInstallData>().on_upgrade({
         let cargo_channel = cargo_worker.channel.clone();
         move |c| {
@@ -160,6 +507,77 @@ fn main() {
                 .unwrap()
         }
     });
+    cargo_ui.global::<RustupData>().on_install({
+        let rustup_channel = rustup_worker.channel.clone();
+        let cargo_ui = cargo_ui.as_weak();
+        move |t| {
+            if t.is_empty() {
+                return;
+            }
+            let toolchains = cargo_ui.unwrap().get_toolchains();
+            if let Some(toolchains) = toolchains
+                .as_any()
+                .downcast_ref::<slint::VecModel<Toolchain>>()
+            {
+                toolchains.push(Toolchain {
+                    name: t.clone(),
+                    queued: true,
+                    ..Default::default()
+                });
+            }
+            rustup_channel.send(RustupMessage::Install(t)).unwrap()
+        }
+    });
+    cargo_ui.global::<RustupData>().on_update({
+        let rustup_channel = rustup_worker.channel.clone();
+        move |t| rustup_channel.send(RustupMessage::Update(t)).unwrap()
+    });
+    cargo_ui.global::<RustupData>().on_uninstall({
+        let rustup_channel = rustup_worker.channel.clone();
+        move |t| rustup_channel.send(RustupMessage::Uninstall(t)).unwrap()
+    });
+    cargo_ui.global::<RustupData>().on_set_default({
+        let rustup_channel = rustup_worker.channel.clone();
+        move |t| rustup_channel.send(RustupMessage::SetDefault(t)).unwrap()
+    });
+    cargo_ui.global::<RustupData>().on_add_component({
+        let rustup_channel = rustup_worker.channel.clone();
+        move |toolchain, component| {
+            rustup_channel
+                .send(RustupMessage::AddComponent {
+                    toolchain,
+                    component,
+                })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<RustupData>().on_remove_component({
+        let rustup_channel = rustup_worker.channel.clone();
+        move |toolchain, component| {
+            rustup_channel
+                .send(RustupMessage::RemoveComponent {
+                    toolchain,
+                    component,
+                })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<RustupData>().on_add_target({
+        let rustup_channel = rustup_worker.channel.clone();
+        move |toolchain, target| {
+            rustup_channel
+                .send(RustupMessage::AddTarget { toolchain, target })
+                .unwrap()
+        }
+    });
+    cargo_ui.global::<RustupData>().on_remove_target({
+        let rustup_channel = rustup_worker.channel.clone();
+        move |toolchain, target| {
+            rustup_channel
+                .send(RustupMessage::RemoveTarget { toolchain, target })
+                .unwrap()
+        }
+    });
     cargo_ui
         .global::<CratesCompletionData>()
         .on_update_completion({
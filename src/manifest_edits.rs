@@ -0,0 +1,541 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use crate::cargo::{dependency_table, to_table_name};
+use crate::dependency_features::resolved_package;
+use anyhow::Context;
+use cargo_metadata::semver::{Version, VersionReq};
+use cargo_metadata::{Dependency, DependencyKind, Metadata, PackageId};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Manifests being edited, which are only written once all the edits succeeded
+#[derive(Default)]
+pub struct ManifestEdits {
+    /// the path, the original content, and the edited document
+    documents: Vec<(PathBuf, String, toml_edit::DocumentMut)>,
+}
+
+impl ManifestEdits {
+    pub fn document(&mut self, path: &Path) -> anyhow::Result<&mut toml_edit::DocumentMut> {
+        let index = match self.documents.iter().position(|(p, ..)| p == path) {
+            Some(index) => index,
+            None => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to load '{}'", path.display()))?;
+                let document = contents
+                    .parse()
+                    .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+                self.documents.push((path.to_owned(), contents, document));
+                self.documents.len() - 1
+            }
+        };
+        Ok(&mut self.documents[index].2)
+    }
+
+    /// The entry of a dependency in the manifest of a package
+    pub fn entry(
+        &mut self,
+        manifest: &Path,
+        dep_kind: DependencyKind,
+        target: Option<&str>,
+        key: &str,
+    ) -> anyhow::Result<&mut toml_edit::Item> {
+        let document = self.document(manifest)?;
+        let table_name = to_table_name(dep_kind);
+        // looked up first, indexing would add the missing tables
+        let table = match target {
+            Some(target) => document
+                .get("target")
+                .and_then(|t| t.get(target))
+                .and_then(|t| t.get(table_name)),
+            None => document.get(table_name),
+        };
+        if table.and_then(|t| t.get(key)).is_none() {
+            anyhow::bail!(
+                "'{}' was not in [{}] of '{}'",
+                key,
+                to_table_name(dep_kind),
+                manifest.display()
+            );
+        }
+        Ok(&mut dependency_table(document, dep_kind, target)[key])
+    }
+
+    /// The entry that declares the version of a dependency: the one of the package, or the one of
+    /// `[workspace.dependencies]` in the root manifest if it is inherited with `workspace = true`
+    pub fn dependency(
+        &mut self,
+        manifest: &Path,
+        workspace_root: &Path,
+        dep_kind: DependencyKind,
+        target: Option<&str>,
+        key: &str,
+    ) -> anyhow::Result<&mut toml_edit::Item> {
+        if !is_inherited(self.entry(manifest, dep_kind, target, key)?) {
+            return self.entry(manifest, dep_kind, target, key);
+        }
+        let root = workspace_root.join("Cargo.toml");
+        let document = self.document(&root)?;
+        let entry = document
+            .get("workspace")
+            .and_then(|w| w.get("dependencies"))
+            .and_then(|d| d.get(key));
+        if entry.is_none() {
+            anyhow::bail!(
+                "'{}' was not in [workspace.dependencies] of '{}'",
+                key,
+                root.display()
+            );
+        }
+        Ok(&mut document["workspace"]["dependencies"][key])
+    }
+
+    /// Write the edited manifests, and return the previous content of those that changed
+    pub fn write(self) -> anyhow::Result<Vec<(PathBuf, String)>> {
+        let mut written: Vec<(PathBuf, String)> = Vec::new();
+        for (path, contents, document) in self.documents {
+            let edited = document.to_string();
+            if edited == contents {
+                continue;
+            }
+            if let Err(e) = std::fs::write(&path, edited) {
+                for (path, contents) in &written {
+                    std::fs::write(path, contents).ok();
+                }
+                return Err(e).with_context(|| format!("Failed to write '{}'", path.display()));
+            }
+            written.push((path, contents));
+        }
+        Ok(written)
+    }
+}
+
+/// `foo = { workspace = true }`
+pub fn is_inherited(entry: &toml_edit::Item) -> bool {
+    entry.get("workspace").and_then(|w| w.as_bool()) == Some(true)
+}
+
+/// Dependencies from crates.io or another registry, rather than from a path or a git repository
+pub fn is_from_registry(dependency: &Dependency) -> bool {
+    dependency
+        .source
+        .as_deref()
+        .is_some_and(|s| s.starts_with("registry+") || s.starts_with("sparse+"))
+}
+
+/// How the direct dependencies are shared between the workspace members
+#[derive(Default)]
+pub struct WorkspaceDependencies {
+    /// The member and the name of the dependencies declared with `workspace = true`
+    pub inherited: HashSet<(PackageId, String)>,
+    /// How many members depend on each crate from a registry
+    pub users: HashMap<String, usize>,
+}
+
+pub fn workspace_dependencies(metadata: &Metadata) -> WorkspaceDependencies {
+    let mut result = WorkspaceDependencies::default();
+    for package in metadata.workspace_packages() {
+        let mut names: Vec<&str> = package
+            .dependencies
+            .iter()
+            .filter(|d| is_from_registry(d))
+            .map(|d| d.name.as_str())
+            .collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            *result.users.entry(name.to_owned()).or_default() += 1;
+        }
+
+        // Errors are ignored: they are reported when the metadata is read
+        let Some(document) = std::fs::read_to_string(&package.manifest_path)
+            .ok()
+            .and_then(|c| c.parse::<toml_edit::DocumentMut>().ok())
+        else {
+            continue;
+        };
+        let targets = document
+            .get("target")
+            .and_then(|t| t.as_table_like())
+            .into_iter()
+            .flat_map(|t| t.iter().map(|(_, t)| t));
+        for table in std::iter::once(document.as_item()).chain(targets) {
+            for kind in [
+                DependencyKind::Normal,
+                DependencyKind::Development,
+                DependencyKind::Build,
+            ] {
+                let Some(dependencies) = table
+                    .get(to_table_name(kind))
+                    .and_then(|d| d.as_table_like())
+                else {
+                    continue;
+                };
+                result.inherited.extend(
+                    dependencies
+                        .iter()
+                        .filter(|(_, entry)| is_inherited(entry))
+                        .map(|(key, _)| (package.id.clone(), key.to_owned())),
+                );
+            }
+        }
+    }
+    result
+}
+
+/// A member that declares a dependency being moved to `[workspace.dependencies]`
+struct Declaration<'a> {
+    manifest: &'a Path,
+    dep_kind: DependencyKind,
+    target: Option<String>,
+    default_features: bool,
+    requirement: String,
+    /// The version in the lockfile, if known
+    resolved: Option<Version>,
+}
+
+/// The keys that a member can give to a dependency that it inherits, besides `workspace`
+const INHERITED_KEYS: [&str; 2] = ["features", "optional"];
+
+/// Move the version of a crate used by several members to `[workspace.dependencies]` of the root
+/// manifest, and make the members inherit it with `workspace = true`. Their features are kept.
+pub fn promote_to_workspace(metadata: &Metadata, crate_name: &str) -> anyhow::Result<()> {
+    let root = metadata.workspace_root.as_std_path().join("Cargo.toml");
+    let mut edits = ManifestEdits::default();
+    if edits.document(&root)?.get("workspace").is_none() {
+        anyhow::bail!("'{}' does not declare a [workspace]", root.display());
+    }
+
+    let mut declarations = Vec::new();
+    for package in metadata.workspace_packages() {
+        for dependency in &package.dependencies {
+            if dependency.name != crate_name
+                || dependency.rename.is_some()
+                || !is_from_registry(dependency)
+            {
+                continue;
+            }
+            let target = dependency.target.as_ref().map(|t| t.to_string());
+            let manifest = package.manifest_path.as_std_path();
+            let entry = edits.entry(manifest, dependency.kind, target.as_deref(), crate_name)?;
+            if is_inherited(entry) {
+                continue;
+            }
+            let requirement = entry
+                .as_str()
+                .or_else(|| entry.get("version")?.as_str())
+                .with_context(|| {
+                    format!(
+                        "'{}' has no version in '{}'",
+                        crate_name,
+                        manifest.display()
+                    )
+                })?
+                .to_owned();
+            let resolved = resolved_package(metadata, package, crate_name, dependency.kind)
+                .ok()
+                .map(|p| p.version.clone());
+            declarations.push(Declaration {
+                manifest,
+                dep_kind: dependency.kind,
+                target,
+                default_features: dependency.uses_default_features,
+                requirement,
+                resolved,
+            });
+        }
+    }
+    promote(&mut edits, &root, crate_name, &declarations)?;
+    edits.write()?;
+    Ok(())
+}
+
+/// Make the declarations inherit `[workspace.dependencies]` of the `root` manifest, adding the
+/// dependency there if needed, with the requirement of the member that uses the most recent
+/// version. The members must all accept that version, so that none of them is moved to a
+/// breaking one.
+///
+/// Members cannot disable the default features of an inherited dependency, so they are disabled
+/// in the workspace if any member disables them, and the members that used them request the
+/// `default` feature instead.
+fn promote(
+    edits: &mut ManifestEdits,
+    root: &Path,
+    crate_name: &str,
+    declarations: &[Declaration],
+) -> anyhow::Result<()> {
+    let existing_entry = edits
+        .document(root)?
+        .get("workspace")
+        .and_then(|w| w.get("dependencies"))
+        .and_then(|d| d.get(crate_name));
+    let existing = existing_entry.map(|entry| {
+        ["default-features", "default_features"]
+            .iter()
+            .any(|key| entry.get(key).and_then(|v| v.as_bool()) == Some(false))
+    });
+    let existing_requirement = existing_entry
+        .and_then(|entry| entry.as_str().or_else(|| entry.get("version")?.as_str()))
+        .map(|r| (r.to_owned(), root));
+
+    // the first member that uses the most recent version
+    let mut highest: Option<(&Declaration, &Version)> = None;
+    for declaration in declarations {
+        let Some(resolved) = &declaration.resolved else {
+            continue;
+        };
+        if highest.is_none_or(|(_, version)| version < resolved) {
+            highest = Some((declaration, resolved));
+        }
+    }
+    let Some(highest) = highest else {
+        anyhow::bail!("No member depends on '{}' from a registry", crate_name);
+    };
+    let requirements = existing_requirement.iter().cloned().chain(
+        declarations
+            .iter()
+            .map(|d| (d.requirement.clone(), d.manifest)),
+    );
+    for (requirement, manifest) in requirements {
+        let compatible = VersionReq::parse(&requirement).is_ok_and(|r| r.matches(highest.1));
+        if !compatible {
+            anyhow::bail!(
+                "The requirement '{}' of '{}' in '{}' does not accept {}, used by '{}'",
+                requirement,
+                crate_name,
+                manifest.display(),
+                highest.1,
+                highest.0.manifest.display()
+            );
+        }
+    }
+    let requirement = highest.0.requirement.clone();
+
+    let without_default_features = match existing {
+        Some(false) if declarations.iter().any(|d| !d.default_features) => anyhow::bail!(
+            "Some members disable the default features of '{}', which [workspace.dependencies] of \
+             '{}' enables",
+            crate_name,
+            root.display()
+        ),
+        Some(without_default_features) => without_default_features,
+        None => declarations.iter().any(|d| !d.default_features),
+    };
+    for declaration in declarations {
+        let entry = edits.entry(
+            declaration.manifest,
+            declaration.dep_kind,
+            declaration.target.as_deref(),
+            crate_name,
+        )?;
+        if let Some(value) = entry.as_value_mut().filter(|v| v.is_str()) {
+            let mut inline = toml_edit::InlineTable::new();
+            // keep the comment that follows the version
+            *inline.decor_mut() = value.decor().clone();
+            *value = toml_edit::Value::InlineTable(inline);
+        }
+        let table = entry
+            .as_table_like_mut()
+            .context("Could not understand the manifest")?;
+        // e.g. `path` or `registry`, which would have to be moved to the workspace too
+        if let Some((key, _)) = table.iter().find(|(key, _)| {
+            !INHERITED_KEYS.contains(key)
+                && !matches!(*key, "version" | "default-features" | "default_features")
+        }) {
+            anyhow::bail!(
+                "'{}' has `{}` in '{}', which an inherited dependency cannot have",
+                crate_name,
+                key,
+                declaration.manifest.display()
+            );
+        }
+        table.remove("version");
+        table.remove("default-features");
+        table.remove("default_features");
+        table.insert("workspace", toml_edit::value(true));
+        if without_default_features && declaration.default_features {
+            match table.get_mut("features").and_then(|f| f.as_array_mut()) {
+                Some(features) => {
+                    if !features.iter().any(|f| f.as_str() == Some("default")) {
+                        features.push("default");
+                    }
+                }
+                None => {
+                    table.insert(
+                        "features",
+                        toml_edit::value(toml_edit::Array::from_iter(["default"])),
+                    );
+                }
+            }
+        }
+        // the spacing of the removed keys is left behind
+        if let Some(inline) = entry.as_inline_table_mut() {
+            inline.fmt();
+        }
+    }
+
+    let dependencies =
+        edits.document(root)?["workspace"]["dependencies"].or_insert(toml_edit::table());
+    // an existing entry is kept, the members now use it
+    if dependencies.get(crate_name).is_none() {
+        dependencies[crate_name] = if without_default_features {
+            let mut inline = toml_edit::InlineTable::new();
+            inline.insert("version", requirement.as_str().into());
+            inline.insert("default-features", false.into());
+            toml_edit::value(inline)
+        } else {
+            toml_edit::value(requirement)
+        };
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_workspace(root: &str, member: &str) -> tempfile::TempDir {
+        let workspace = tempfile::tempdir().unwrap();
+        let dir = workspace.path();
+        std::fs::create_dir(dir.join("member")).unwrap();
+        std::fs::write(dir.join("Cargo.toml"), root).unwrap();
+        std::fs::write(dir.join("member").join("Cargo.toml"), member).unwrap();
+        workspace
+    }
+
+    #[test]
+    fn inherited_dependency_is_edited_in_the_workspace() {
+        let workspace = temp_workspace(
+            "[workspace]\n\n[workspace.dependencies]\nfoo = \"1.0\"\n",
+            "[package]\nname = \"member\"\n\n[target.'cfg(unix)'.dependencies]\nfoo = { workspace = true }\nbar = \"0.1\"\n",
+        );
+        let dir = workspace.path();
+        let member = dir.join("member").join("Cargo.toml");
+        let mut edits = ManifestEdits::default();
+        let entry = edits
+            .dependency(
+                &member,
+                dir,
+                DependencyKind::Normal,
+                Some("cfg(unix)"),
+                "foo",
+            )
+            .unwrap();
+        crate::cargo::set_dependency_version(entry, "1.2").unwrap();
+        let entry = edits
+            .dependency(
+                &member,
+                dir,
+                DependencyKind::Normal,
+                Some("cfg(unix)"),
+                "bar",
+            )
+            .unwrap();
+        crate::cargo::set_dependency_version(entry, "0.2").unwrap();
+        assert!(edits
+            .dependency(&member, dir, DependencyKind::Development, None, "foo")
+            .is_err());
+        assert_eq!(edits.write().unwrap().len(), 2);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("Cargo.toml")).unwrap(),
+            "[workspace]\n\n[workspace.dependencies]\nfoo = \"1.2\"\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&member).unwrap(),
+            "[package]\nname = \"member\"\n\n[target.'cfg(unix)'.dependencies]\nfoo = { workspace = true }\nbar = \"0.2\"\n"
+        );
+    }
+
+    #[test]
+    fn promote_keeps_the_default_features_of_each_member() {
+        let workspace = temp_workspace(
+            "[workspace]\nmembers = [\"member\"]\n\n[dependencies]\nfoo = { version = \"1.0\", features = [\"std\"] }\n",
+            "[package]\nname = \"member\"\n\n[dependencies]\nfoo = { version = \"1.1\", default-features = false }\n",
+        );
+        let dir = workspace.path();
+        let root = dir.join("Cargo.toml");
+        let member = dir.join("member").join("Cargo.toml");
+        let declarations = [
+            Declaration {
+                manifest: &root,
+                dep_kind: DependencyKind::Normal,
+                target: None,
+                default_features: true,
+                requirement: "1.0".to_owned(),
+                resolved: Version::parse("1.0.5").ok(),
+            },
+            Declaration {
+                manifest: &member,
+                dep_kind: DependencyKind::Normal,
+                target: None,
+                default_features: false,
+                requirement: "1.1".to_owned(),
+                resolved: Version::parse("1.1.0").ok(),
+            },
+        ];
+        let mut edits = ManifestEdits::default();
+        promote(&mut edits, &root, "foo", &declarations).unwrap();
+        edits.write().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&root).unwrap(),
+            "[workspace]\nmembers = [\"member\"]\n\n[workspace.dependencies]\nfoo = { version = \"1.1\", default-features = false }\n\n[dependencies]\nfoo = { features = [\"std\", \"default\"], workspace = true }\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&member).unwrap(),
+            "[package]\nname = \"member\"\n\n[dependencies]\nfoo = { workspace = true }\n"
+        );
+
+        // the existing entry of the workspace enables them, so they cannot be disabled
+        std::fs::write(
+            &member,
+            "[package]\nname = \"member\"\n\n[dependencies]\nfoo = { version = \"1.1\", default-features = false }\n",
+        )
+        .unwrap();
+        let mut edits = ManifestEdits::default();
+        edits.document(&root).unwrap().get_mut("workspace").unwrap()["dependencies"]["foo"] =
+            toml_edit::value("1.1");
+        assert!(promote(&mut edits, &root, "foo", &declarations[1..]).is_err());
+    }
+
+    #[test]
+    fn promote_refuses_breaking_versions_and_conflicting_keys() {
+        let workspace = temp_workspace(
+            "[workspace]\nmembers = [\"member\"]\n\n[dependencies]\nfoo = \"0.5\"\n",
+            "[package]\nname = \"member\"\n\n[dependencies]\nfoo = { version = \"1.0\", registry = \"mine\" }\n",
+        );
+        let dir = workspace.path();
+        let root = dir.join("Cargo.toml");
+        let member = dir.join("member").join("Cargo.toml");
+        let declaration = |manifest, requirement: &str, resolved: &str| Declaration {
+            manifest,
+            dep_kind: DependencyKind::Normal,
+            target: None,
+            default_features: true,
+            requirement: requirement.to_owned(),
+            resolved: Version::parse(resolved).ok(),
+        };
+
+        let error = promote(
+            &mut ManifestEdits::default(),
+            &root,
+            "foo",
+            &[
+                declaration(&root, "0.5", "0.5.3"),
+                declaration(&member, "1.0", "1.0.2"),
+            ],
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("'0.5'"), "{}", error);
+
+        let error = promote(
+            &mut ManifestEdits::default(),
+            &root,
+            "foo",
+            &[declaration(&member, "1.0", "1.0.2")],
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("`registry`"), "{}", error);
+    }
+}
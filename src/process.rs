@@ -0,0 +1,62 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use std::process::ExitStatus;
+
+/// Put the command in its own process group, so that the processes it starts (rustc, build
+/// scripts, the program being run) can be stopped with it
+pub fn new_process_group(command: &mut tokio::process::Command) {
+    #[cfg(unix)]
+    command.process_group(0);
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// Ask the process group started by `pid` to stop (SIGINT), or kill it when `force` is set.
+///
+/// On Windows, the processes are always killed: without `/F`, taskkill only closes windows, which
+/// cargo and rustc do not have.
+pub fn stop_process_group(pid: u32, force: bool) {
+    #[cfg(unix)]
+    {
+        let signal = if force { libc::SIGKILL } else { libc::SIGINT };
+        // SAFETY: kill has no memory safety requirements, a negative pid targets the group
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), signal);
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = force;
+        let _ = std::process::Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .output();
+    }
+}
+
+/// Kills the process group when dropped, unless it was disarmed because the process exited
+pub struct ProcessGroupGuard(pub Option<u32>);
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            stop_process_group(pid, true);
+        }
+    }
+}
+
+/// How the process ended, e.g. `exit code 101` or `signal 9`
+pub fn describe_exit_status(status: &ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exit code {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("signal {}", signal);
+        }
+    }
+    "unknown exit status".into()
+}
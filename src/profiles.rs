@@ -0,0 +1,157 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{CargoProfile, CargoUI, ProfileData};
+use anyhow::Context;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+use std::path::Path;
+use std::rc::Rc;
+
+/// The profiles that cargo always knows about, even if they are not in the manifest
+const BUILTIN: [&str; 4] = ["dev", "release", "test", "bench"];
+
+/// The arguments to build with the given profile
+pub fn to_args(profile: &str) -> Vec<&str> {
+    match profile {
+        // "debug" is how the dev profile was called by older versions of cargo-ui
+        "" | "dev" | "debug" => vec![],
+        "release" => vec!["--release"],
+        profile => vec!["--profile", profile],
+    }
+}
+
+fn value_to_string(value: &toml_edit::Value) -> SharedString {
+    match value {
+        toml_edit::Value::String(s) => s.value().into(),
+        value => value.to_string().trim().into(),
+    }
+}
+
+/// Booleans and integers are written as such, so that `opt-level = 3` or `lto = true` stay valid
+fn string_to_value(value: &str) -> toml_edit::Value {
+    if let Ok(b) = value.parse::<bool>() {
+        b.into()
+    } else if let Ok(i) = value.parse::<i64>() {
+        i.into()
+    } else {
+        value.into()
+    }
+}
+
+fn profile_from_table(name: &str, table: Option<&dyn toml_edit::TableLike>) -> CargoProfile {
+    let get = |key: &str| {
+        table
+            .and_then(|t| t.get(key))
+            .and_then(|v| v.as_value())
+            .map(value_to_string)
+            .unwrap_or_default()
+    };
+    CargoProfile {
+        name: name.into(),
+        inherits: get("inherits"),
+        opt_level: get("opt-level"),
+        debug: get("debug"),
+        lto: get("lto"),
+        codegen_units: get("codegen-units"),
+        panic: get("panic"),
+        strip: get("strip"),
+    }
+}
+
+fn load_document(path: &Path) -> anyhow::Result<toml_edit::DocumentMut> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to load '{}'", path.display()))?;
+    contents
+        .parse()
+        .with_context(|| format!("Failed to parse '{}'", path.display()))
+}
+
+/// The built-in profiles and the ones of the `[profile]` section of the workspace's manifest
+pub fn load(workspace_root: &Path) -> anyhow::Result<Vec<CargoProfile>> {
+    let document = load_document(&workspace_root.join("Cargo.toml"))?;
+    let section = document.get("profile").and_then(|p| p.as_table_like());
+    let table = |name: &str| {
+        section
+            .and_then(|s| s.get(name))
+            .and_then(|t| t.as_table_like())
+    };
+    let mut profiles: Vec<CargoProfile> = BUILTIN
+        .iter()
+        .map(|name| profile_from_table(name, table(name)))
+        .collect();
+    if let Some(section) = section {
+        profiles.extend(
+            section
+                .iter()
+                .filter(|(name, _)| !BUILTIN.contains(name))
+                .map(|(name, _)| profile_from_table(name, table(name))),
+        );
+    }
+    Ok(profiles)
+}
+
+/// Write the settings of a profile in the `[profile.<name>]` section of the workspace's manifest.
+/// The empty settings are removed, so that they are inherited.
+pub fn save(workspace_root: &Path, profile: &CargoProfile) -> anyhow::Result<()> {
+    let name = profile.name.trim();
+    if name.is_empty() {
+        anyhow::bail!("The profile needs a name");
+    }
+    if !BUILTIN.contains(&name) && profile.inherits.is_empty() {
+        anyhow::bail!(
+            "The profile '{}' needs to inherit from another profile",
+            name
+        );
+    }
+    let path = workspace_root.join("Cargo.toml");
+    let mut document = load_document(&path)?;
+    let section = document["profile"].or_insert(toml_edit::table());
+    if let Some(section) = section.as_table_mut() {
+        // keep `[profile.*]` headers rather than an empty `[profile]` one
+        section.set_implicit(true);
+    }
+    let table = section[name].or_insert(toml_edit::table());
+    let Some(table) = table.as_table_like_mut() else {
+        anyhow::bail!("[profile.{}] is not a table", name);
+    };
+    for (key, value) in [
+        ("inherits", &profile.inherits),
+        ("opt-level", &profile.opt_level),
+        ("debug", &profile.debug),
+        ("lto", &profile.lto),
+        ("codegen-units", &profile.codegen_units),
+        ("panic", &profile.panic),
+        ("strip", &profile.strip),
+    ] {
+        let value = value.trim();
+        if key == "inherits" && BUILTIN.contains(&name) {
+            continue;
+        }
+        if value.is_empty() {
+            table.remove(key);
+        } else if key == "inherits" || key == "panic" {
+            table.insert(key, toml_edit::value(value));
+        } else {
+            table.insert(key, toml_edit::Item::Value(string_to_value(value)));
+        }
+    }
+    std::fs::write(&path, document.to_string().as_bytes())
+        .with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// Show the profiles in the UI
+pub fn apply(profiles: Vec<CargoProfile>, handle: slint::Weak<CargoUI>) {
+    handle
+        .upgrade_in_event_loop(move |h| {
+            let names: Vec<SharedString> = profiles.iter().map(|p| p.name.clone()).collect();
+            let data = h.global::<ProfileData>();
+            data.set_names(ModelRc::from(
+                Rc::new(VecModel::from(names)) as Rc<dyn Model<Data = SharedString>>
+            ));
+            data.set_profiles(ModelRc::from(
+                Rc::new(VecModel::from(profiles)) as Rc<dyn Model<Data = CargoProfile>>
+            ));
+        })
+        .unwrap();
+}
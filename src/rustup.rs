@@ -2,16 +2,58 @@
  * SPDX-License-Identifier: MIT OR Apache-2.0
  */
 
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 
 use super::{CargoUI, Toolchain};
+use futures::future::{Fuse, FusedFuture, FutureExt};
 use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RustupMessage {
     Quit,
+    /// Install the toolchain with the given name (e.g. `nightly` or `1.75`)
+    Install(SharedString),
+    Update(SharedString),
+    Uninstall(SharedString),
+    /// Make the given toolchain the rustup default
+    SetDefault(SharedString),
+    AddComponent {
+        toolchain: SharedString,
+        component: SharedString,
+    },
+    RemoveComponent {
+        toolchain: SharedString,
+        component: SharedString,
+    },
+    /// Add the standard library for a cross compilation target
+    AddTarget {
+        toolchain: SharedString,
+        target: SharedString,
+    },
+    RemoveTarget {
+        toolchain: SharedString,
+        target: SharedString,
+    },
+}
+
+impl RustupMessage {
+    /// The toolchain a job operates on
+    fn toolchain(&self) -> Option<&SharedString> {
+        match self {
+            RustupMessage::Quit => None,
+            RustupMessage::Install(t)
+            | RustupMessage::Update(t)
+            | RustupMessage::Uninstall(t)
+            | RustupMessage::SetDefault(t) => Some(t),
+            RustupMessage::AddComponent { toolchain, .. }
+            | RustupMessage::RemoveComponent { toolchain, .. }
+            | RustupMessage::AddTarget { toolchain, .. }
+            | RustupMessage::RemoveTarget { toolchain, .. } => Some(toolchain),
+        }
+    }
 }
 
 pub struct RustupWorker {
@@ -43,21 +85,96 @@ impl RustupWorker {
 }
 
 async fn rustup_worker_loop(mut r: UnboundedReceiver<RustupMessage>, handle: slint::Weak<CargoUI>) {
-    let refresh_handle = tokio::task::spawn(refresh_toolchains(handle.clone()));
-    if let Some(RustupMessage::Quit) = r.recv().await {
-        refresh_handle.abort();
+    // Jobs are run one after the other, since rustup operations don't like to overlap
+    let mut job_queue = VecDeque::new();
+    let mut current_job: Option<RustupMessage> = None;
+    // Error message of the last failed job, per toolchain
+    let mut failures = HashMap::new();
+
+    let refresh_future = refresh_toolchains().fuse();
+    let process_job_future = Fuse::terminated();
+    futures::pin_mut!(refresh_future, process_job_future);
+
+    loop {
+        let m = futures::select! {
+            res = refresh_future => {
+                match res {
+                    Ok(toolchains) => apply_toolchains(toolchains, &job_queue, current_job.as_ref(), &failures, handle.clone()),
+                    Err(e) => handle
+                        .clone()
+                        .upgrade_in_event_loop(move |h| {
+                            h.set_status(format!("Error while listing toolchains: {:#}", e).into())
+                        })
+                        .unwrap(),
+                }
+                continue;
+            }
+            res = process_job_future => {
+                if let Some(toolchain) = current_job.take().as_ref().and_then(|j| j.toolchain()) {
+                    match res {
+                        Ok(()) => { failures.remove(toolchain); }
+                        Err(e) => { failures.insert(toolchain.clone(), SharedString::from(format!("Failed: {}", e))); }
+                    }
+                }
+                if let Some(job) = job_queue.pop_front() {
+                    current_job = Some(job.clone());
+                    process_job_future.set(process_job(job, handle.clone()).fuse());
+                }
+                refresh_future.set(refresh_toolchains().fuse());
+                continue;
+            }
+            m = r.recv().fuse() => {
+                match m {
+                    None | Some(RustupMessage::Quit) => return,
+                    Some(m) => m,
+                }
+            }
+        };
+
+        if process_job_future.is_terminated() {
+            current_job = Some(m.clone());
+            process_job_future.set(process_job(m, handle.clone()).fuse());
+        } else {
+            job_queue.push_back(m);
+        }
     }
 }
 
-async fn refresh_toolchains(handle: slint::Weak<CargoUI>) -> tokio::io::Result<()> {
-    handle
-        .clone()
-        .upgrade_in_event_loop(|ui| {
-            ui.set_toolchains_available(false);
-        })
-        .unwrap();
+/// The data of a [`Toolchain`], which can be sent to the UI thread
+#[derive(Default)]
+struct ToolchainInfo {
+    name: SharedString,
+    default: bool,
+    queued: bool,
+    status: SharedString,
+    components: Vec<SharedString>,
+    targets: Vec<SharedString>,
+}
 
-    let mut rustup_command = tokio::process::Command::new("rustup");
+impl From<ToolchainInfo> for Toolchain {
+    fn from(info: ToolchainInfo) -> Self {
+        Toolchain {
+            name: info.name,
+            default: info.default,
+            queued: info.queued,
+            status: info.status,
+            components: ModelRc::from(
+                Rc::new(VecModel::from(info.components)) as Rc<dyn Model<Data = SharedString>>
+            ),
+            targets: ModelRc::from(
+                Rc::new(VecModel::from(info.targets)) as Rc<dyn Model<Data = SharedString>>
+            ),
+            expanded: false,
+        }
+    }
+}
+
+fn rustup_command() -> tokio::process::Command {
+    tokio::process::Command::new("rustup")
+}
+
+async fn refresh_toolchains() -> tokio::io::Result<Vec<ToolchainInfo>> {
+    let mut rustup_command = rustup_command();
     rustup_command.arg("toolchain").arg("list");
     let mut spawn_result = rustup_command
         .stdout(std::process::Stdio::piped())
@@ -69,21 +186,293 @@ async fn refresh_toolchains(handle: slint::Weak<CargoUI>) -> tokio::io::Result<(
     let mut toolchains = Vec::new();
 
     while let Some(line) = stdout.next_line().await? {
-        let name: SharedString = line.into();
-        toolchains.push({
-            let default = name.contains("(default)");
-            Toolchain { name, default }
+        // Lines look like `stable-x86_64-unknown-linux-gnu (active, default)`
+        let (name, flags) = line.split_once(' ').unwrap_or((&line, ""));
+        toolchains.push(ToolchainInfo {
+            name: name.into(),
+            default: flags.contains("default"),
+            ..Default::default()
+        });
+    }
+
+    // All the toolchains are queried at once, they can take a while each
+    futures::future::join_all(toolchains.iter_mut().map(list_installed)).await;
+
+    Ok(toolchains)
+}
+
+/// Fill in the installed components and targets of the toolchain
+async fn list_installed(toolchain: &mut ToolchainInfo) {
+    // Errors are ignored: a broken toolchain just shows no component
+    let (targets, components) = futures::join!(
+        rustup_list(&["target", "list", "--installed"], &toolchain.name),
+        rustup_list(&["component", "list", "--installed"], &toolchain.name),
+    );
+    let targets = targets.unwrap_or_default();
+    let components = components.unwrap_or_default();
+
+    // Components are suffixed with the host triple, which is also the suffix of the toolchain name
+    let host_suffix = targets
+        .iter()
+        .map(|t| format!("-{}", t))
+        .find(|suffix| toolchain.name.ends_with(suffix.as_str()));
+    toolchain.components = components
+        .iter()
+        // the standard libraries are shown as targets
+        .filter(|c| !c.starts_with("rust-std-"))
+        .map(|c| {
+            host_suffix
+                .as_ref()
+                .and_then(|suffix| c.strip_suffix(suffix.as_str()))
+                .unwrap_or(c)
+                .into()
+        })
+        .collect();
+    toolchain.targets = targets.iter().map(SharedString::from).collect();
+}
+
+/// Run `rustup <args> --toolchain <toolchain>` and return the lines of its output
+async fn rustup_list(args: &[&str], toolchain: &str) -> tokio::io::Result<Vec<String>> {
+    let output = rustup_command()
+        .args(args)
+        .arg("--toolchain")
+        .arg(toolchain)
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.trim().to_owned())
+        .collect())
+}
+
+fn apply_toolchains(
+    mut toolchains: Vec<ToolchainInfo>,
+    job_queue: &VecDeque<RustupMessage>,
+    current_job: Option<&RustupMessage>,
+    failures: &HashMap<SharedString, SharedString>,
+    handle: slint::Weak<CargoUI>,
+) {
+    // The first, empty, entry stands for the rustup default toolchain
+    let toolchain_names: Vec<SharedString> = std::iter::once(SharedString::default())
+        .chain(toolchains.iter().map(|t| t.name.clone()))
+        .collect();
+
+    let mut pending: HashSet<_> = job_queue
+        .iter()
+        .chain(current_job)
+        .filter_map(|job| job.toolchain())
+        .collect();
+    for t in toolchains.iter_mut() {
+        t.queued = pending.remove(&t.name);
+        t.status = failures.get(&t.name).cloned().unwrap_or_default();
+    }
+    // Toolchains that are being installed, or that failed to install, are not listed by rustup
+    for (name, status) in failures {
+        if !pending.contains(name) && !toolchains.iter().any(|t| t.name == *name) {
+            toolchains.push(ToolchainInfo {
+                name: name.clone(),
+                status: status.clone(),
+                ..Default::default()
+            });
+        }
+    }
+    for name in pending {
+        toolchains.push(ToolchainInfo {
+            name: name.clone(),
+            queued: true,
+            ..Default::default()
         });
     }
 
     handle
         .upgrade_in_event_loop(|ui| {
+            // Keep the rows that the user expanded open
+            let previous = ui.get_toolchains();
+            let toolchains: Vec<Toolchain> = toolchains
+                .into_iter()
+                .map(|info| {
+                    let mut t = Toolchain::from(info);
+                    t.expanded = previous.iter().any(|p| p.expanded && p.name == t.name);
+                    t
+                })
+                .collect();
+            ui.set_toolchain_names(ModelRc::from(
+                Rc::new(VecModel::from(toolchain_names)) as Rc<dyn Model<Data = SharedString>>
+            ));
             ui.set_toolchains(ModelRc::from(
                 Rc::new(VecModel::from(toolchains)) as Rc<dyn Model<Data = Toolchain>>
             ));
             ui.set_toolchains_available(true);
         })
         .unwrap();
+}
+
+/// The rustup command that performs the job
+fn job_command(job: &RustupMessage) -> Option<tokio::process::Command> {
+    let mut cmd = rustup_command();
+    match job {
+        RustupMessage::Install(t) => cmd.arg("toolchain").arg("install").arg(t.as_str()),
+        RustupMessage::Update(t) => cmd.arg("update").arg(t.as_str()),
+        RustupMessage::Uninstall(t) => cmd.arg("toolchain").arg("uninstall").arg(t.as_str()),
+        RustupMessage::SetDefault(t) => cmd.arg("default").arg(t.as_str()),
+        RustupMessage::AddComponent {
+            toolchain,
+            component,
+        } => cmd
+            .args(["component", "add", "--toolchain"])
+            .args([toolchain.as_str(), component.as_str()]),
+        RustupMessage::RemoveComponent {
+            toolchain,
+            component,
+        } => cmd
+            .args(["component", "remove", "--toolchain"])
+            .args([toolchain.as_str(), component.as_str()]),
+        RustupMessage::AddTarget { toolchain, target } => cmd
+            .args(["target", "add", "--toolchain"])
+            .args([toolchain.as_str(), target.as_str()]),
+        RustupMessage::RemoveTarget { toolchain, target } => cmd
+            .args(["target", "remove", "--toolchain"])
+            .args([toolchain.as_str(), target.as_str()]),
+        RustupMessage::Quit => return None,
+    };
+    Some(cmd)
+}
 
+async fn process_job(job: RustupMessage, handle: slint::Weak<CargoUI>) -> anyhow::Result<()> {
+    let Some(cmd) = job_command(&job) else {
+        return Ok(());
+    };
+    let toolchain = job.toolchain().cloned().unwrap_or_default();
+    run_job(cmd, |status| {
+        let toolchain = toolchain.clone();
+        handle
+            .clone()
+            .upgrade_in_event_loop(move |ui| {
+                let toolchains = ui.get_toolchains();
+                for i in 0..toolchains.row_count() {
+                    if let Some(mut t) = toolchains.row_data(i) {
+                        if t.name == toolchain {
+                            t.queued = true;
+                            t.status = status.into();
+                            toolchains.set_row_data(i, t);
+                            return;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+    })
+    .await
+}
+
+/// Run the command, passing each line of its output to `on_status`. The error is the last line,
+/// which is where rustup explains why it failed.
+async fn run_job(
+    mut cmd: tokio::process::Command,
+    mut on_status: impl FnMut(String),
+) -> anyhow::Result<()> {
+    let mut res = cmd
+        .kill_on_drop(true)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+
+    let mut stdout = BufReader::new(res.stdout.take().unwrap()).lines();
+    let mut stderr = BufReader::new(res.stderr.take().unwrap()).lines();
+    let mut last_line = String::new();
+    // rustup reports its progress and errors on stderr, which may go on after stdout is closed
+    let (mut stdout_done, mut stderr_done) = (false, false);
+    while !stdout_done || !stderr_done {
+        let line = tokio::select! {
+            line = stderr.next_line(), if !stderr_done => line?.or_else(|| {
+                stderr_done = true;
+                None
+            }),
+            line = stdout.next_line(), if !stdout_done => line?.or_else(|| {
+                stdout_done = true;
+                None
+            }),
+        };
+        let Some(status) = line.filter(|l| !l.trim().is_empty()) else {
+            continue;
+        };
+        last_line.clone_from(&status);
+        on_status(status);
+    }
+
+    let exit_status = res.wait().await?;
+    if !exit_status.success() {
+        anyhow::bail!("{}", last_line.trim_start_matches("error: "));
+    }
     Ok(())
 }
+
+// the fake rustup is a shell script
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A directory with a `rustup` script, to put on the PATH of the command
+    fn fake_rustup(script: &str) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rustup");
+        std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    async fn run(dir: &std::path::Path, job: RustupMessage) -> (anyhow::Result<()>, Vec<String>) {
+        let mut cmd = job_command(&job).unwrap();
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let paths = std::iter::once(dir.to_owned()).chain(std::env::split_paths(&path));
+        cmd.env("PATH", std::env::join_paths(paths).unwrap());
+        let mut statuses = Vec::new();
+        let result = run_job(cmd, |s| statuses.push(s)).await;
+        (result, statuses)
+    }
+
+    #[tokio::test]
+    async fn install_progress() {
+        let dir = fake_rustup(
+            r#"[ "$*" = "toolchain install nightly" ] || exit 2
+echo "info: syncing channel updates for 'nightly'" >&2
+echo "info: downloading component 'rustc'" >&2
+echo
+echo "  nightly installed - rustc 1.80.0-nightly"
+"#,
+        );
+        let (result, statuses) = run(dir.path(), RustupMessage::Install("nightly".into())).await;
+        result.unwrap();
+        assert_eq!(statuses.len(), 3);
+        assert!(statuses.contains(&"info: downloading component 'rustc'".to_owned()));
+        assert_eq!(
+            statuses.iter().filter(|s| s.contains("installed")).count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn failure_message_after_stdout_is_closed() {
+        let dir = fake_rustup(
+            r#"echo "info: syncing channel updates for 'nightly-2000-01-01'" >&2
+exec 1>&-
+sleep 0.2
+echo "error: toolchain 'nightly-2000-01-01' is not installable" >&2
+exit 1
+"#,
+        );
+        let (result, statuses) = run(
+            dir.path(),
+            RustupMessage::Install("nightly-2000-01-01".into()),
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "toolchain 'nightly-2000-01-01' is not installable"
+        );
+        assert_eq!(statuses.len(), 2);
+    }
+}
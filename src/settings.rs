@@ -0,0 +1,254 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{Action, RunConfiguration};
+use crate::environment::parse_env;
+use anyhow::Context;
+use itertools::Itertools;
+use slint::SharedString;
+use std::path::{Path, PathBuf};
+
+/// Name of the file, next to the workspace's Cargo.toml, in which the settings are stored
+const SETTINGS_FILE_NAME: &str = ".cargo-ui.toml";
+
+/// Key of the array of tables of the run configurations
+const RUN_CONFIGURATIONS: &str = "run-configuration";
+
+/// Settings that are remembered for a workspace.
+///
+/// The file is edited with `toml_edit` so that comments and formatting are kept.
+pub struct WorkspaceSettings {
+    path: PathBuf,
+    document: toml_edit::DocumentMut,
+}
+
+impl WorkspaceSettings {
+    /// Load the settings of the workspace in `workspace_root`. A missing file means default settings.
+    pub fn load(workspace_root: &Path) -> anyhow::Result<Self> {
+        let path = workspace_root.join(SETTINGS_FILE_NAME);
+        let document = if path.exists() {
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to load '{}'", path.display()))?
+                .parse()
+                .with_context(|| format!("Failed to parse '{}'", path.display()))?
+        } else {
+            Default::default()
+        };
+        Ok(Self { path, document })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        std::fs::write(&self.path, self.document.to_string().as_bytes())
+            .with_context(|| format!("Failed to write '{}'", self.path.display()))
+    }
+
+    fn get_str(&self, key: &str) -> SharedString {
+        self.document
+            .get(key)
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .into()
+    }
+
+    /// Set a string value, an empty string removes the key
+    fn set_str(&mut self, key: &str, value: &str) {
+        if value.is_empty() {
+            self.document.remove(key);
+        } else {
+            self.document[key] = toml_edit::value(value);
+        }
+    }
+
+    /// The toolchain to use for this workspace, or an empty string for the rustup default
+    pub fn toolchain(&self) -> SharedString {
+        self.get_str("toolchain")
+    }
+
+    pub fn set_toolchain(&mut self, toolchain: &str) {
+        self.set_str("toolchain", toolchain);
+    }
+
+    /// `nextest` to run the tests with cargo-nextest, or an empty string for `cargo test`
+    pub fn test_runner(&self) -> SharedString {
+        self.get_str("test-runner")
+    }
+
+    pub fn set_test_runner(&mut self, runner: &str) {
+        self.set_str("test-runner", runner);
+    }
+
+    /// The nextest profile (from `.config/nextest.toml`) to run the tests with
+    pub fn nextest_profile(&self) -> SharedString {
+        self.get_str("nextest-profile")
+    }
+
+    pub fn set_nextest_profile(&mut self, profile: &str) {
+        self.set_str("nextest-profile", profile);
+    }
+
+    /// The run configurations saved for this workspace, in the order of the file
+    pub fn run_configurations(&self) -> Vec<RunConfiguration> {
+        self.document
+            .get(RUN_CONFIGURATIONS)
+            .and_then(|c| c.as_array_of_tables())
+            .map(|c| c.iter().map(run_configuration_from_table).collect())
+            .unwrap_or_default()
+    }
+
+    /// Add a run configuration, or replace the one with the same name
+    pub fn set_run_configuration(&mut self, configuration: &RunConfiguration) {
+        let table = run_configuration_to_table(configuration);
+        if !self.document.contains_key(RUN_CONFIGURATIONS) {
+            self.document[RUN_CONFIGURATIONS] = toml_edit::Item::ArrayOfTables(Default::default());
+        }
+        let Some(configurations) = self.document[RUN_CONFIGURATIONS].as_array_of_tables_mut()
+        else {
+            return;
+        };
+        let existing = configurations
+            .iter()
+            .position(|t| t.get("name").and_then(|n| n.as_str()) == Some(&configuration.name));
+        match existing {
+            Some(index) => *configurations.get_mut(index).unwrap() = table,
+            None => configurations.push(table),
+        }
+    }
+
+    pub fn remove_run_configuration(&mut self, name: &str) {
+        if let Some(configurations) = self
+            .document
+            .get_mut(RUN_CONFIGURATIONS)
+            .and_then(|c| c.as_array_of_tables_mut())
+        {
+            configurations.retain(|t| t.get("name").and_then(|n| n.as_str()) != Some(name));
+        }
+    }
+
+    /// Command used to open a file in an editor, with `{file}`, `{line}` and `{col}` placeholders
+    /// (e.g. `code -g {file}:{line}:{col}`)
+    pub fn editor(&self) -> Option<String> {
+        self.document
+            .get("editor")
+            .and_then(|e| e.as_str())
+            .map(|e| e.to_owned())
+    }
+}
+
+fn run_configuration_from_table(table: &toml_edit::Table) -> RunConfiguration {
+    let get = |key: &str| -> SharedString {
+        table
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .into()
+    };
+    let features = table
+        .get("features")
+        .and_then(|f| f.as_array())
+        .map(|f| {
+            f.iter()
+                .filter_map(|f| Some(f.as_str()?.trim()))
+                .filter(|f| !f.is_empty())
+                .join(",")
+        })
+        .unwrap_or_default();
+    let env = table
+        .get("env")
+        .and_then(|e| e.as_table_like())
+        .map(|e| {
+            e.iter()
+                .filter_map(|(key, value)| Some(format!("{}={}", key, value.as_str()?)))
+                .join("\n")
+        })
+        .unwrap_or_default();
+    RunConfiguration {
+        name: get("name"),
+        action: Action {
+            command: get("command"),
+            package: get("package"),
+            profile: get("profile"),
+            toolchain: get("toolchain"),
+            extra: get("target"),
+            target_triple: get("target-triple"),
+            arguments: get("arguments"),
+            env: env.into(),
+            working_directory: get("working-directory"),
+        },
+        features: features.into(),
+        default_features: table
+            .get("default-features")
+            .and_then(|d| d.as_bool())
+            .unwrap_or(true),
+    }
+}
+
+fn run_configuration_to_table(configuration: &RunConfiguration) -> toml_edit::Table {
+    let action = &configuration.action;
+    let mut table = toml_edit::Table::new();
+    table["name"] = toml_edit::value(configuration.name.as_str());
+    table["command"] = toml_edit::value(action.command.as_str());
+    // only write the values that are set, to keep the file short
+    for (key, value) in [
+        ("package", &action.package),
+        ("target", &action.extra),
+        ("target-triple", &action.target_triple),
+        ("profile", &action.profile),
+        ("toolchain", &action.toolchain),
+        ("arguments", &action.arguments),
+        ("working-directory", &action.working_directory),
+    ] {
+        if !value.is_empty() {
+            table[key] = toml_edit::value(value.as_str());
+        }
+    }
+    // as typed in the UI, e.g. `a, b,`
+    let features: toml_edit::Array = configuration
+        .features
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .collect();
+    if !features.is_empty() {
+        table["features"] = toml_edit::value(features);
+    }
+    if !configuration.default_features {
+        table["default-features"] = toml_edit::value(false);
+    }
+    let env = parse_env(&configuration.action.env);
+    if !env.is_empty() {
+        let mut env_table = toml_edit::InlineTable::new();
+        for (key, value) in env {
+            env_table.insert(&key, value.into());
+        }
+        table["env"] = toml_edit::value(env_table);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_are_trimmed() {
+        let configuration = RunConfiguration {
+            name: "server".into(),
+            action: Action {
+                command: "run".into(),
+                ..Default::default()
+            },
+            features: " tls, ,json ,".into(),
+            default_features: true,
+        };
+        let table = run_configuration_to_table(&configuration);
+        assert_eq!(table["features"].to_string().trim(), r#"["tls", "json"]"#);
+        assert_eq!(run_configuration_from_table(&table).features, "tls,json");
+
+        let empty = RunConfiguration {
+            features: " , ".into(),
+            ..configuration
+        };
+        assert!(!run_configuration_to_table(&empty).contains_key("features"));
+    }
+}
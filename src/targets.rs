@@ -0,0 +1,163 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::CargoUI;
+use slint::{Model, ModelRc, SharedString, VecModel};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The settings of the cargo configuration files that matter for the target selection
+#[derive(Default)]
+struct CargoConfig {
+    /// `build.target`
+    build_target: Option<String>,
+    /// the triples with a `target.<triple>.runner`
+    runners: Vec<String>,
+}
+
+/// The configuration files, from the most specific to the least specific one
+fn config_files(directory: &Path) -> Vec<PathBuf> {
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cargo")));
+    directory
+        .ancestors()
+        .map(|d| d.join(".cargo"))
+        .chain(cargo_home)
+        .flat_map(|d| [d.join("config.toml"), d.join("config")])
+        .filter(|f| f.is_file())
+        .collect()
+}
+
+fn read_cargo_config(directory: &Path) -> CargoConfig {
+    let mut config = CargoConfig {
+        build_target: std::env::var("CARGO_BUILD_TARGET").ok(),
+        ..Default::default()
+    };
+    for file in config_files(directory) {
+        // Errors are ignored: cargo reports them when building
+        let Some(document) = std::fs::read_to_string(&file)
+            .ok()
+            .and_then(|c| c.parse::<toml_edit::DocumentMut>().ok())
+        else {
+            continue;
+        };
+        if config.build_target.is_none() {
+            let target = document.get("build").and_then(|b| b.get("target"));
+            // with several targets, the first one is shown as the default
+            config.build_target = target
+                .and_then(|t| t.as_str())
+                .or_else(|| target?.as_array()?.get(0)?.as_str())
+                .map(|t| t.to_owned());
+        }
+        if let Some(targets) = document.get("target").and_then(|t| t.as_table_like()) {
+            config.runners.extend(
+                targets
+                    .iter()
+                    .filter(|(triple, table)| {
+                        !triple.starts_with("cfg(") && table.get("runner").is_some()
+                    })
+                    .map(|(triple, _)| triple.to_owned()),
+            );
+        }
+    }
+    config
+}
+
+async fn host_triple(directory: &Path, toolchain: &str) -> Option<String> {
+    let mut rustc = tokio::process::Command::new("rustc");
+    if !toolchain.is_empty() {
+        rustc.env("RUSTUP_TOOLCHAIN", toolchain);
+    }
+    let output = rustc
+        .arg("-vV")
+        .current_dir(directory)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|l| l.strip_prefix("host: "))
+        .map(|h| h.trim().to_owned())
+}
+
+async fn installed_targets(directory: &Path, toolchain: &str) -> Vec<String> {
+    let mut rustup = tokio::process::Command::new("rustup");
+    rustup.args(["target", "list", "--installed"]);
+    if !toolchain.is_empty() {
+        rustup.arg("--toolchain").arg(toolchain);
+    }
+    let Ok(output) = rustup
+        .current_dir(directory)
+        .kill_on_drop(true)
+        .output()
+        .await
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.trim().to_owned())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Whether the programs built for `target` can be started on the `host`: either directly, when
+/// the architecture and the operating system are the same (e.g. `x86_64-unknown-linux-musl` on
+/// `x86_64-unknown-linux-gnu`), or through a runner configured for that target
+fn is_runnable(target: &str, host: &str, runners: &[String]) -> bool {
+    fn arch_and_os(triple: &str) -> (Option<&str>, Option<&str>) {
+        let parts: Vec<&str> = triple.split('-').collect();
+        (parts.first().copied(), parts.get(2).copied())
+    }
+    target == host
+        || runners.iter().any(|r| r == target)
+        || std::env::var_os(format!(
+            "CARGO_TARGET_{}_RUNNER",
+            target.to_uppercase().replace(['-', '.'], "_")
+        ))
+        .is_some()
+        || arch_and_os(target) == arch_and_os(host)
+}
+
+/// Find the targets that can be built in the workspace of `directory`, and show them in the UI.
+/// The first one is the default: `build.target` from the cargo configuration, or the host.
+pub async fn read_targets(
+    directory: PathBuf,
+    toolchain: SharedString,
+    handle: slint::Weak<CargoUI>,
+) {
+    let config = read_cargo_config(&directory);
+    let host = host_triple(&directory, &toolchain)
+        .await
+        .unwrap_or_default();
+    let mut triples = Vec::new();
+    for triple in config
+        .build_target
+        .iter()
+        .chain(std::iter::once(&host))
+        .chain(installed_targets(&directory, &toolchain).await.iter())
+    {
+        if !triple.is_empty() && !triples.contains(triple) {
+            triples.push(triple.clone());
+        }
+    }
+    let runnable: Vec<bool> = triples
+        .iter()
+        .map(|t| is_runnable(t, &host, &config.runners))
+        .collect();
+    let triples: Vec<SharedString> = triples.iter().map(|t| t.as_str().into()).collect();
+
+    handle
+        .upgrade_in_event_loop(move |h| {
+            h.set_target_triples(ModelRc::from(
+                Rc::new(VecModel::from(triples)) as Rc<dyn Model<Data = SharedString>>
+            ));
+            h.set_target_runnable(ModelRc::from(
+                Rc::new(VecModel::from(runnable)) as Rc<dyn Model<Data = bool>>
+            ));
+        })
+        .unwrap();
+}
@@ -0,0 +1,815 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{CargoUI, TestData, TestResult};
+use crate::cargo::{self, FeatureSettings};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Values of `TestResult::status`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestStatus {
+    NotRun = 0,
+    Running = 1,
+    Passed = 2,
+    Failed = 3,
+    Ignored = 4,
+}
+
+/// How the tests are run, as chosen in the Tests tab
+#[derive(Debug, Clone, Default)]
+pub struct TestRunner {
+    /// Use `cargo nextest run` instead of `cargo test`
+    pub nextest: bool,
+    pub profile: SharedString,
+    /// A nextest filter expression, such as `test(parse) & !test(slow)`
+    pub filter: SharedString,
+}
+
+impl TestRunner {
+    pub fn new(ui: &CargoUI) -> Self {
+        let data = ui.global::<TestData>();
+        Self {
+            nextest: data.get_runner() == NEXTEST,
+            profile: data.get_nextest_profile(),
+            filter: data.get_nextest_filter(),
+        }
+    }
+
+    /// The options of `cargo nextest run` and `cargo nextest list`
+    pub fn nextest_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if !self.profile.trim().is_empty() {
+            args.extend(["--profile".to_owned(), self.profile.trim().to_owned()]);
+        }
+        if !self.filter.trim().is_empty() {
+            args.extend(["-E".to_owned(), self.filter.trim().to_owned()]);
+        }
+        args
+    }
+}
+
+/// The value of `TestData::runner` for nextest
+const NEXTEST: &str = "cargo nextest";
+
+/// Show the test settings of the workspace in the UI
+pub fn apply_settings(ui: &CargoUI, runner: SharedString, profile: SharedString) {
+    let data = ui.global::<TestData>();
+    data.set_runner(
+        if runner == "nextest" {
+            NEXTEST
+        } else {
+            "cargo test"
+        }
+        .into(),
+    );
+    data.set_nextest_profile(profile);
+}
+
+/// The value stored in the workspace settings for the test runner
+pub fn runner_setting(runner: &TestRunner) -> &'static str {
+    if runner.nextest {
+        "nextest"
+    } else {
+        ""
+    }
+}
+
+/// What the output of libtest tells about a test
+#[derive(Debug, Default)]
+pub struct TestEvent {
+    pub name: String,
+    /// The target of the test, if known, as in `TestResult::target`
+    pub target: Option<String>,
+    pub status: Option<TestStatus>,
+    /// in seconds, only reported by the json format
+    pub duration: Option<f64>,
+    pub output: Option<String>,
+}
+
+impl TestEvent {
+    /// The line libtest prints for this event, for the json format that is not human readable
+    pub fn summary_line(&self) -> Option<String> {
+        let result = match self.status? {
+            TestStatus::Passed => "ok",
+            TestStatus::Failed => "FAILED",
+            TestStatus::Ignored => "ignored",
+            TestStatus::NotRun | TestStatus::Running => return None,
+        };
+        Some(format!("test {} ... {}", self.name, result))
+    }
+}
+
+/// Parse the output of the test harness, line by line.
+///
+/// Both the default human readable format and the unstable json format
+/// (`-Z unstable-options --format json`) are understood.
+#[derive(Default)]
+pub struct TestOutputParser {
+    /// The test whose captured output is being read, in the `failures:` section
+    captured: Option<(String, String)>,
+}
+
+impl TestOutputParser {
+    pub fn parse_line(&mut self, line: &str) -> Option<TestEvent> {
+        if is_json(line) {
+            return parse_json_event(line);
+        }
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|l| l.strip_suffix(" stdout ----"))
+        {
+            let previous = self.take_captured();
+            self.captured = Some((name.to_owned(), String::new()));
+            return previous;
+        }
+        if let Some((_, output)) = &mut self.captured {
+            // The list of the names of the failed tests follows their output
+            if line == "failures:" || line == "successes:" || line.starts_with("test result: ") {
+                return self.take_captured();
+            }
+            output.push_str(line);
+            output.push('\n');
+            return None;
+        }
+        let (name, result) = line.strip_prefix("test ")?.rsplit_once(" ... ")?;
+        let status = if result.starts_with("ok") {
+            TestStatus::Passed
+        } else if result.starts_with("FAILED") {
+            TestStatus::Failed
+        } else if result.starts_with("ignored") {
+            TestStatus::Ignored
+        } else {
+            return None;
+        };
+        Some(TestEvent {
+            // `should_panic` tests are reported as `name - should panic`
+            name: name.trim_end_matches(" - should panic").to_owned(),
+            status: Some(status),
+            ..Default::default()
+        })
+    }
+
+    fn take_captured(&mut self) -> Option<TestEvent> {
+        let (name, output) = self.captured.take()?;
+        Some(TestEvent {
+            name,
+            output: Some(output.trim_end().to_owned()),
+            ..Default::default()
+        })
+    }
+}
+
+/// Whether the line is in the json format of libtest
+pub fn is_json(line: &str) -> bool {
+    line.starts_with('{') && line.contains("\"type\"")
+}
+
+fn parse_json_event(line: &str) -> Option<TestEvent> {
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    if value.get("type")?.as_str()? != "test" {
+        // suite and bench events
+        return None;
+    }
+    let status = match value.get("event")?.as_str()? {
+        "started" => TestStatus::Running,
+        "ok" => TestStatus::Passed,
+        "failed" | "timeout" => TestStatus::Failed,
+        "ignored" => TestStatus::Ignored,
+        _ => return None,
+    };
+    let name = value.get("name")?.as_str()?;
+    // nextest prefixes the names with the id of the test binary: `crate::bin/name$test`
+    let (target, name) = match name.split_once('$') {
+        Some((binary_id, name)) => (Some(binary_id_target(binary_id)), name),
+        None => (None, name),
+    };
+    Some(TestEvent {
+        name: name.to_owned(),
+        target,
+        status: Some(status),
+        duration: value.get("exec_time").and_then(|t| t.as_f64()),
+        output: value
+            .get("stdout")
+            .and_then(|o| o.as_str())
+            .map(|o| o.trim_end().to_owned()),
+    })
+}
+
+fn format_duration(seconds: f64) -> String {
+    if seconds < 1. {
+        format!("{:.0} ms", seconds * 1000.)
+    } else {
+        format!("{:.2} s", seconds)
+    }
+}
+
+/// Show the result of a test in the Tests tab, adding it if it was not listed
+pub fn apply_test_event(ui: &CargoUI, event: TestEvent) {
+    let model_handle = ui.global::<TestData>().get_tests();
+    let Some(model) = model_handle.as_any().downcast_ref::<VecModel<TestResult>>() else {
+        return;
+    };
+    // Without the target, the result is for the first test of that name that is still running
+    let matches = |t: &TestResult| {
+        t.name == event.name.as_str()
+            && event
+                .target
+                .as_ref()
+                .is_none_or(|target| t.target == target.as_str())
+    };
+    let row = model
+        .iter()
+        .position(|t| matches(&t) && t.status == TestStatus::Running as i32)
+        .or_else(|| model.iter().position(|t| matches(&t)));
+    let row = match row {
+        Some(row) => row,
+        None => {
+            model.push(TestResult {
+                name: event.name.as_str().into(),
+                target: event.target.unwrap_or_default().into(),
+                ..Default::default()
+            });
+            model.row_count() - 1
+        }
+    };
+    let mut test = model.row_data(row).unwrap();
+    if let Some(status) = event.status {
+        test.status = status as i32;
+        if status == TestStatus::Running {
+            test.output = Default::default();
+        }
+    }
+    if let Some(duration) = event.duration {
+        test.duration = format_duration(duration).into();
+    }
+    if let Some(output) = event.output {
+        test.output = output.into();
+    }
+    model.set_row_data(row, test);
+}
+
+/// Run the tests matching `filter`, by starting a `cargo test` (or `cargo nextest run`) from the
+/// Project tab
+pub fn run_tests(ui: &CargoUI, filter: impl Fn(&TestResult) -> bool) {
+    let runner = TestRunner::new(ui);
+    let data = ui.global::<TestData>();
+    if runner.nextest && !data.get_nextest_available() {
+        data.set_status("cargo-nextest is not installed".into());
+        return;
+    }
+    let tests = data.get_tests();
+    let rows: Vec<usize> = (0..tests.row_count())
+        .filter(|row| tests.row_data(*row).is_some_and(|t| filter(&t)))
+        .collect();
+    if rows.is_empty() {
+        data.set_status("No test to run".into());
+        return;
+    }
+
+    let mut arguments = Vec::new();
+    // only the target of the tests is built and run, if they are all in the same one
+    let mut target = SharedString::default();
+    if rows.len() < tests.row_count() {
+        let selected: Vec<TestResult> =
+            rows.iter().filter_map(|row| tests.row_data(*row)).collect();
+        if selected.iter().all(|t| t.target == selected[0].target) {
+            target = selected[0].target.clone();
+        }
+        arguments.extend(selected.into_iter().map(|t| t.name));
+        arguments.push("--exact".into());
+    }
+    if data.get_nightly() && !runner.nextest {
+        // gives the duration of each test
+        for arg in [
+            "-Z",
+            "unstable-options",
+            "--format",
+            "json",
+            "--report-time",
+        ] {
+            arguments.push(arg.into());
+        }
+    }
+    let Ok(arguments) = shlex::try_join(arguments.iter().map(SharedString::as_str)) else {
+        data.set_status("Invalid test name".into());
+        return;
+    };
+    let mut nextest_args = runner.nextest_args();
+    nextest_args.extend(target_args(&target));
+    let Ok(nextest_args) = shlex::try_join(nextest_args.iter().map(String::as_str)) else {
+        data.set_status("Invalid filter expression".into());
+        return;
+    };
+
+    for row in rows {
+        let mut test = tests.row_data(row).unwrap();
+        test.status = TestStatus::Running as i32;
+        tests.set_row_data(row, test);
+    }
+    data.set_status("Running tests...".into());
+    if runner.nextest {
+        ui.invoke_run_tests("nextest".into(), nextest_args.into(), arguments.into());
+    } else {
+        ui.invoke_run_tests("test".into(), target, arguments.into());
+    }
+}
+
+/// Called when `cargo test` is done: the tests that did not report a result were not run
+pub fn finish_test_run(ui: &CargoUI) {
+    let data = ui.global::<TestData>();
+    let tests = data.get_tests();
+    let mut count = HashMap::new();
+    for row in 0..tests.row_count() {
+        let mut test = tests.row_data(row).unwrap();
+        if test.status == TestStatus::Running as i32 {
+            test.status = TestStatus::NotRun as i32;
+            tests.set_row_data(row, test.clone());
+        }
+        *count.entry(test.status).or_insert(0) += 1;
+    }
+    let count = |status: TestStatus| count.get(&(status as i32)).copied().unwrap_or(0);
+    data.set_status(
+        format!(
+            "{} passed; {} failed; {} ignored",
+            count(TestStatus::Passed),
+            count(TestStatus::Failed),
+            count(TestStatus::Ignored)
+        )
+        .into(),
+    );
+}
+
+/// Whether the toolchain is a nightly, whose test harness supports the json output
+async fn is_nightly(toolchain: &str, directory: Option<PathBuf>) -> bool {
+    let mut command = tokio::process::Command::new("rustc");
+    command.arg("--version");
+    if !toolchain.is_empty() {
+        command.env("RUSTUP_TOOLCHAIN", toolchain);
+    }
+    if let Some(directory) = directory {
+        command.current_dir(directory);
+    }
+    match command.kill_on_drop(true).output().await {
+        Ok(output) => {
+            let version = String::from_utf8_lossy(&output.stdout);
+            version.contains("-nightly") || version.contains("-dev")
+        }
+        Err(_) => false,
+    }
+}
+
+/// Build the tests of `package` and list them with `cargo test -- --list` (or `cargo nextest list`),
+/// for the Tests tab
+pub async fn list_tests(
+    manifest_path: PathBuf,
+    package: SharedString,
+    toolchain: SharedString,
+    features: FeatureSettings,
+    runner: TestRunner,
+    handle: slint::Weak<CargoUI>,
+) {
+    handle
+        .clone()
+        .upgrade_in_event_loop(|ui| {
+            let data = ui.global::<TestData>();
+            data.set_listing(true);
+            data.set_status("Building the tests...".into());
+        })
+        .unwrap();
+
+    let directory = manifest_path.parent().map(|d| d.to_owned());
+    let nightly = is_nightly(&toolchain, directory.clone()).await;
+    let result = list_test_names(
+        manifest_path,
+        directory,
+        &package,
+        &toolchain,
+        &features,
+        &runner,
+    )
+    .await;
+
+    handle
+        .upgrade_in_event_loop(move |ui| {
+            let data = ui.global::<TestData>();
+            data.set_listing(false);
+            data.set_nightly(nightly);
+            match result {
+                Ok(names) => {
+                    // keep the results of the tests that were already run
+                    let previous: HashMap<(SharedString, SharedString), TestResult> = data
+                        .get_tests()
+                        .iter()
+                        .map(|t| ((t.target.clone(), t.name.clone()), t))
+                        .collect();
+                    let tests: Vec<TestResult> = names
+                        .into_iter()
+                        .map(|(target, name)| {
+                            let key = (SharedString::from(target), SharedString::from(name));
+                            previous.get(&key).cloned().unwrap_or(TestResult {
+                                target: key.0,
+                                name: key.1,
+                                ..Default::default()
+                            })
+                        })
+                        .collect();
+                    data.set_status(format!("{} tests", tests.len()).into());
+                    data.set_tests(ModelRc::from(Rc::new(VecModel::from(tests))));
+                }
+                Err(e) => data.set_status(e.into()),
+            }
+        })
+        .unwrap();
+}
+
+/// The target and the name of the tests. libtest does not tell the target of the tests it lists,
+/// so the test executables are built first and listed one by one.
+async fn list_test_names(
+    manifest_path: PathBuf,
+    directory: Option<PathBuf>,
+    package: &str,
+    toolchain: &str,
+    features: &FeatureSettings,
+    runner: &TestRunner,
+) -> Result<Vec<(String, String)>, String> {
+    let cargo_command = |args: &[&str]| {
+        let mut command = cargo::cargo_command(toolchain);
+        command
+            .args(args)
+            .arg("--manifest-path")
+            .arg(&manifest_path);
+        if let Some(directory) = &directory {
+            command.current_dir(directory);
+        }
+        if !package.is_empty() {
+            command.arg("-p").arg(package);
+        }
+        features.to_args(&mut command);
+        command
+    };
+
+    let mut names = if runner.nextest {
+        let mut command = cargo_command(&["nextest", "list"]);
+        command.args(runner.nextest_args());
+        command.args(["--message-format", "json"]);
+        parse_nextest_list(&output_of(command).await?)
+            .ok_or_else(|| "Failed to parse the list of tests of nextest".to_owned())?
+    } else {
+        let build = cargo_command(&["test", "--no-run", "--message-format", "json"]);
+        let executables = test_executables(&output_of(build).await?);
+        let mut names = Vec::new();
+        for (target, executable) in &executables {
+            let mut command = tokio::process::Command::new(executable);
+            command.arg("--list");
+            if let Some(directory) = &directory {
+                command.current_dir(directory);
+            }
+            names.extend(
+                parse_libtest_list(&output_of(command).await?)
+                    .into_iter()
+                    .map(|name| (target.clone(), name)),
+            );
+        }
+        // the documentation tests of each library, one package at a time to tell them apart
+        for target in executables.iter().map(|(target, _)| target) {
+            let Some(package) = target.strip_suffix(LIB).and_then(|p| p.strip_suffix(": ")) else {
+                continue;
+            };
+            let mut doc = cargo_command(&["test", "--doc", "-p", package]);
+            doc.args(["--", "--list"]);
+            names.extend(
+                parse_libtest_list(&output_of(doc).await?)
+                    .into_iter()
+                    .map(|name| (target_label(package, "doc", ""), name)),
+            );
+        }
+        names
+    };
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Run the command and return its output, or the last error it printed if it failed
+async fn output_of(mut command: tokio::process::Command) -> Result<String, String> {
+    let output = command
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {:?}: {}", command.as_std().get_program(), e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.lines().rfind(|l| l.starts_with("error")) {
+            Some(error) => format!("Failed to list the tests: {}", error),
+            None => format!("Failed to list the tests: {}", output.status),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// How the unit tests of a library, and its documentation tests, are labelled in a target
+const LIB: &str = "(lib)";
+const DOC: &str = "(doc)";
+
+/// The target of the tests of a target of this kind, as in `TestResult::target`: `package: (lib)`,
+/// `package: (doc)`, or `package: name (bin)` for a binary, and alike for the tests, the
+/// benchmarks and the examples
+fn target_label(package: &str, kind: &str, name: &str) -> String {
+    match kind {
+        "bin" | "test" | "bench" | "example" => format!("{}: {} ({})", package, name, kind),
+        "doc" => format!("{}: {}", package, DOC),
+        _ => format!("{}: {}", package, LIB),
+    }
+}
+
+/// The options of cargo test (or cargo nextest run) that select the target of the tests. A plain
+/// name is an integration test, as given by the test target selection of the Project tab.
+pub fn target_args(target: &str) -> Vec<String> {
+    if target.is_empty() {
+        return Vec::new();
+    }
+    let (mut args, target) = match target.split_once(": ") {
+        Some((package, target)) => (vec!["-p".to_owned(), package.to_owned()], target),
+        None => (Vec::new(), target),
+    };
+    match target {
+        LIB => args.push("--lib".to_owned()),
+        DOC => args.push("--doc".to_owned()),
+        _ => {
+            let (name, kind) = target
+                .strip_suffix(')')
+                .and_then(|t| t.rsplit_once(" ("))
+                .unwrap_or((target, "test"));
+            args.extend([format!("--{}", kind), name.to_owned()]);
+        }
+    }
+    args
+}
+
+/// The target of a nextest binary id: `package` for the library, `package::name` for an
+/// integration test, and `package::bin/name` for the others
+fn binary_id_target(binary_id: &str) -> String {
+    match binary_id.split_once("::") {
+        None => target_label(binary_id, "lib", ""),
+        Some((package, rest)) => match rest.split_once('/') {
+            Some((kind, name)) => target_label(package, kind, name),
+            None => target_label(package, "test", rest),
+        },
+    }
+}
+
+/// The test executables built by `cargo test --no-run --message-format json`, with their target
+fn test_executables(json_lines: &str) -> Vec<(String, PathBuf)> {
+    json_lines
+        .lines()
+        .filter_map(|line| match serde_json::from_str(line).ok()? {
+            cargo_metadata::Message::CompilerArtifact(artifact) if artifact.profile.test => {
+                let kind = artifact.target.kind.first()?.to_string();
+                Some((
+                    target_label(
+                        cargo::package_name(&artifact.package_id),
+                        &kind,
+                        &artifact.target.name,
+                    ),
+                    artifact.executable?.into_std_path_buf(),
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The names of the tests in the output of the `--list` option of libtest. The benchmarks are
+/// listed as `name: benchmark`
+fn parse_libtest_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_suffix(": test"))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Get the target and the name of the tests from the output of
+/// `cargo nextest list --message-format json`, leaving out those that do not match the filter
+/// expression
+fn parse_nextest_list(json: &str) -> Option<Vec<(String, String)>> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    let mut names = Vec::new();
+    for (binary_id, suite) in value.get("rust-suites")?.as_object()? {
+        // as in the names of the events of the tests
+        let target = binary_id_target(binary_id);
+        for (name, test) in suite.get("testcases")?.as_object()? {
+            let matches = test
+                .get("filter-match")
+                .and_then(|m| m.get("status"))
+                .and_then(|s| s.as_str())
+                .is_none_or(|s| s == "matches");
+            if matches {
+                names.push((target.clone(), name.clone()));
+            }
+        }
+    }
+    Some(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn human_readable_output() {
+        let mut parser = TestOutputParser::default();
+        let mut events = Vec::new();
+        for line in [
+            "running 3 tests",
+            "test parse::empty ... ok",
+            "test parse::panics - should panic ... ok",
+            "test parse::fails ... FAILED",
+            "test slow ... ignored, too slow",
+            "",
+            "failures:",
+            "",
+            "---- parse::fails stdout ----",
+            "thread 'parse::fails' panicked at src/parse.rs:10:5:",
+            "assertion failed",
+            "",
+            "",
+            "failures:",
+            "    parse::fails",
+            "",
+            "test result: FAILED. 2 passed; 1 failed; 1 ignored",
+        ] {
+            events.extend(parser.parse_line(line));
+        }
+        let summary: Vec<(&str, Option<TestStatus>)> =
+            events.iter().map(|e| (e.name.as_str(), e.status)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("parse::empty", Some(TestStatus::Passed)),
+                ("parse::panics", Some(TestStatus::Passed)),
+                ("parse::fails", Some(TestStatus::Failed)),
+                ("slow", Some(TestStatus::Ignored)),
+                ("parse::fails", None),
+            ]
+        );
+        assert_eq!(
+            events[4].output.as_deref(),
+            Some("thread 'parse::fails' panicked at src/parse.rs:10:5:\nassertion failed")
+        );
+        assert!(events.iter().all(|e| e.target.is_none()));
+    }
+
+    #[test]
+    fn libtest_json_output() {
+        let mut parser = TestOutputParser::default();
+        assert!(parser
+            .parse_line(r#"{ "type": "suite", "event": "started", "test_count": 2 }"#)
+            .is_none());
+        let started = parser
+            .parse_line(r#"{ "type": "test", "event": "started", "name": "a::b" }"#)
+            .unwrap();
+        assert_eq!(started.status, Some(TestStatus::Running));
+        let failed = parser
+            .parse_line(r#"{ "type": "test", "name": "a::b", "event": "failed", "exec_time": 0.25, "stdout": "oops\n" }"#)
+            .unwrap();
+        assert_eq!(failed.name, "a::b");
+        assert_eq!(failed.status, Some(TestStatus::Failed));
+        assert_eq!(failed.duration, Some(0.25));
+        assert_eq!(failed.output.as_deref(), Some("oops"));
+        assert_eq!(failed.summary_line().unwrap(), "test a::b ... FAILED");
+    }
+
+    #[test]
+    fn nextest_json_output() {
+        let mut parser = TestOutputParser::default();
+        let event = |parser: &mut TestOutputParser, name: &str| {
+            let line = format!(r#"{{"type":"test","event":"ok","name":"{}"}}"#, name);
+            let event = parser.parse_line(&line).unwrap();
+            (event.target.unwrap(), event.name)
+        };
+        assert_eq!(
+            event(&mut parser, "my-crate$tests::it_works"),
+            ("my-crate: (lib)".to_owned(), "tests::it_works".to_owned())
+        );
+        assert_eq!(
+            event(&mut parser, "my-crate::bin/cli$args"),
+            ("my-crate: cli (bin)".to_owned(), "args".to_owned())
+        );
+        assert_eq!(
+            event(&mut parser, "my-crate::integration$end_to_end"),
+            (
+                "my-crate: integration (test)".to_owned(),
+                "end_to_end".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn nextest_list() {
+        let json = r#"{
+            "rust-suites": {
+                "my-crate": {
+                    "kind": "lib", "binary-name": "my_crate",
+                    "testcases": {
+                        "tests::a": { "filter-match": { "status": "matches" } },
+                        "tests::b": { "filter-match": { "status": "mismatch", "reason": "expression" } }
+                    }
+                },
+                "my-crate::integration": {
+                    "kind": "test", "binary-name": "integration",
+                    "testcases": { "tests::a": { "filter-match": { "status": "matches" } } }
+                },
+                "other": {
+                    "kind": "lib", "binary-name": "other",
+                    "testcases": { "tests::a": { "filter-match": { "status": "matches" } } }
+                }
+            }
+        }"#;
+        let mut names = parse_nextest_list(json).unwrap();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                ("my-crate: (lib)".to_owned(), "tests::a".to_owned()),
+                (
+                    "my-crate: integration (test)".to_owned(),
+                    "tests::a".to_owned()
+                ),
+                ("other: (lib)".to_owned(), "tests::a".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_executables_of_a_workspace() {
+        let artifact = |package: &str, kind: &str, name: &str| {
+            serde_json::json!({
+                "reason": "compiler-artifact",
+                "package_id": format!("path+file:///ws/{}#0.1.0", package),
+                "manifest_path": format!("/ws/{}/Cargo.toml", package),
+                "target": {
+                    "kind": [kind], "crate_types": [kind], "name": name,
+                    "src_path": "/ws/src/lib.rs", "edition": "2021",
+                    "doctest": true, "test": true, "doc": true
+                },
+                "profile": {
+                    "opt_level": "0", "debuginfo": 2, "debug_assertions": true,
+                    "overflow_checks": true, "test": true
+                },
+                "features": [],
+                "filenames": [format!("/ws/target/debug/deps/{}", name)],
+                "executable": format!("/ws/target/debug/deps/{}", name),
+                "fresh": false
+            })
+            .to_string()
+        };
+        let output = [
+            artifact("core", "lib", "core"),
+            artifact("app", "lib", "app"),
+            artifact("app", "test", "integration"),
+        ]
+        .join("\n");
+        let targets: Vec<String> = test_executables(&output)
+            .into_iter()
+            .map(|(target, _)| target)
+            .collect();
+        assert_eq!(
+            targets,
+            ["core: (lib)", "app: (lib)", "app: integration (test)"]
+        );
+    }
+
+    #[test]
+    fn libtest_list() {
+        let output =
+            "tests::a: test\ntests::b: test\nbench_parse: benchmark\n\n2 tests, 1 benchmark\n";
+        assert_eq!(parse_libtest_list(output), ["tests::a", "tests::b"]);
+    }
+
+    #[test]
+    fn target_selection() {
+        assert!(target_args("").is_empty());
+        assert_eq!(target_args("core: (lib)"), ["-p", "core", "--lib"]);
+        assert_eq!(target_args("core: (doc)"), ["-p", "core", "--doc"]);
+        assert_eq!(target_args("app: cli (bin)"), ["-p", "app", "--bin", "cli"]);
+        assert_eq!(
+            target_args("app: integration (test)"),
+            ["-p", "app", "--test", "integration"]
+        );
+        assert_eq!(target_args("integration"), ["--test", "integration"]);
+        assert_eq!(binary_id_target("my-crate"), "my-crate: (lib)");
+        assert_eq!(
+            binary_id_target("my-crate::bench/parse"),
+            "my-crate: parse (bench)"
+        );
+        assert_eq!(
+            binary_id_target("my-crate::integration"),
+            "my-crate: integration (test)"
+        );
+    }
+}
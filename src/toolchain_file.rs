@@ -0,0 +1,204 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use super::{CargoUI, ToolchainFileData, ToolchainOverride};
+use anyhow::Context;
+use itertools::Itertools;
+use slint::{ComponentHandle, SharedString};
+use std::path::{Path, PathBuf};
+
+/// The file names that rustup looks for, in order of preference
+const FILE_NAMES: [&str; 2] = ["rust-toolchain.toml", "rust-toolchain"];
+
+/// A `rust-toolchain.toml` (or legacy `rust-toolchain`) file that overrides the toolchain for a directory
+pub struct ToolchainFile {
+    path: PathBuf,
+    document: toml_edit::DocumentMut,
+}
+
+impl ToolchainFile {
+    /// Find the toolchain file that applies to `directory`, the same way rustup does: by looking
+    /// into the directory and its parents.
+    pub fn find(directory: &Path) -> Option<PathBuf> {
+        directory
+            .ancestors()
+            .flat_map(|dir| FILE_NAMES.iter().map(move |name| dir.join(name)))
+            .find(|path| path.is_file())
+    }
+
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to load '{}'", path.display()))?;
+        let document = if !contents.contains('[') && !contents.trim().is_empty() {
+            // The legacy format only contains the name of the channel
+            let mut document = toml_edit::DocumentMut::new();
+            document["toolchain"]["channel"] = toml_edit::value(contents.trim());
+            document
+        } else {
+            contents
+                .parse()
+                .with_context(|| format!("Failed to parse '{}'", path.display()))?
+        };
+        Ok(Self { path, document })
+    }
+
+    /// A file that does not exist yet, in `directory`
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            path: directory.join(FILE_NAMES[0]),
+            document: Default::default(),
+        }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        std::fs::write(&self.path, self.document.to_string().as_bytes())
+            .with_context(|| format!("Failed to write '{}'", self.path.display()))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn get_str(&self, key: &str) -> SharedString {
+        self.document
+            .get("toolchain")
+            .and_then(|t| t.get(key))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .into()
+    }
+
+    /// Arrays are shown to the user as comma separated lists
+    fn get_list(&self, key: &str) -> SharedString {
+        self.document
+            .get("toolchain")
+            .and_then(|t| t.get(key))
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|v| v.as_str()).join(", "))
+            .unwrap_or_default()
+            .into()
+    }
+
+    fn set_str(&mut self, key: &str, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            if let Some(t) = self
+                .document
+                .get_mut("toolchain")
+                .and_then(|t| t.as_table_like_mut())
+            {
+                t.remove(key);
+            }
+        } else if self.get_str(key) != value {
+            self.document["toolchain"][key] = toml_edit::value(value);
+        }
+    }
+
+    fn set_list(&mut self, key: &str, value: &str) {
+        let values: Vec<&str> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .collect();
+        if values.is_empty() {
+            if let Some(t) = self
+                .document
+                .get_mut("toolchain")
+                .and_then(|t| t.as_table_like_mut())
+            {
+                t.remove(key);
+            }
+        } else if self.get_list(key) != values.join(", ") {
+            self.document["toolchain"][key] =
+                toml_edit::value(values.into_iter().collect::<toml_edit::Array>());
+        }
+    }
+
+    pub fn to_override(&self) -> ToolchainOverride {
+        ToolchainOverride {
+            channel: self.get_str("channel"),
+            profile: self.get_str("profile"),
+            components: self.get_list("components"),
+            targets: self.get_list("targets"),
+        }
+    }
+
+    /// Apply the values edited by the user. Keys that are not changed are left untouched.
+    pub fn apply_override(&mut self, o: &ToolchainOverride) {
+        self.set_str("channel", &o.channel);
+        self.set_str("profile", &o.profile);
+        self.set_list("components", &o.components);
+        self.set_list("targets", &o.targets);
+    }
+}
+
+/// Ask rustup which toolchain is active in `directory`, and why
+/// (e.g. `nightly-x86_64-unknown-linux-gnu (overridden by '/path/rust-toolchain.toml')`)
+async fn active_toolchain(directory: &Path) -> Option<String> {
+    let output = tokio::process::Command::new("rustup")
+        .arg("show")
+        .arg("active-toolchain")
+        .current_dir(directory)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    Some(output.lines().next()?.trim().to_owned())
+}
+
+/// Read the toolchain file and the active toolchain of the workspace in `directory`,
+/// and show them in the UI
+pub async fn read_toolchain_file(
+    directory: PathBuf,
+    workspace_root: PathBuf,
+    handle: slint::Weak<CargoUI>,
+) {
+    let file = match ToolchainFile::find(&directory) {
+        Some(path) => ToolchainFile::load(path),
+        None => Ok(ToolchainFile::new(&workspace_root)),
+    };
+    let (exists, path, toolchain_override, error) = match file {
+        Ok(file) => (
+            file.path().exists(),
+            file.path().display().to_string(),
+            file.to_override(),
+            String::new(),
+        ),
+        Err(e) => (true, String::new(), Default::default(), format!("{:#}", e)),
+    };
+    let active = active_toolchain(&directory).await.unwrap_or_default();
+    let (active_name, active_reason) = active.split_once(' ').unwrap_or((&active, ""));
+    let (active_name, active_reason) = (active_name.to_owned(), active_reason.to_owned());
+
+    handle
+        .upgrade_in_event_loop(move |ui| {
+            let data = ui.global::<ToolchainFileData>();
+            data.set_available(true);
+            data.set_file_exists(exists);
+            data.set_path(path.into());
+            data.set_error(error.into());
+            data.set_active_toolchain(active_name.into());
+            data.set_active_reason(active_reason.into());
+            data.set_channel(toolchain_override.channel);
+            data.set_profile(toolchain_override.profile);
+            data.set_components(toolchain_override.components);
+            data.set_targets(toolchain_override.targets);
+        })
+        .unwrap();
+}
+
+/// Write the values edited by the user to the toolchain file of the workspace
+pub fn save_toolchain_file(
+    directory: &Path,
+    workspace_root: &Path,
+    toolchain_override: &ToolchainOverride,
+) -> anyhow::Result<()> {
+    let mut file = match ToolchainFile::find(directory) {
+        Some(path) => ToolchainFile::load(path)?,
+        None => ToolchainFile::new(workspace_root),
+    };
+    file.apply_override(toolchain_override);
+    file.save()
+}
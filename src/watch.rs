@@ -0,0 +1,128 @@
+/* Copyright © SixtyFPS GmbH <info@slint-ui.com>
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ */
+
+use cargo_metadata::Metadata;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// How often the files are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// A change is only reported once the files stopped changing for that long, so that saving
+/// several files at once only triggers one run
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The manifests, and the directories of the sources of the targets, of the selected package, or
+/// of all the workspace members if none is selected
+fn watched_paths(metadata: &Metadata, package: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = metadata
+        .workspace_packages()
+        .into_iter()
+        .filter(|p| package.is_empty() || p.name == package)
+        .flat_map(|p| {
+            std::iter::once(p.manifest_path.clone().into_std_path_buf()).chain(
+                p.targets
+                    .iter()
+                    .filter_map(|t| Some(t.src_path.parent()?.to_owned().into_std_path_buf())),
+            )
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+    // src/bin is already watched with src
+    let roots = paths.clone();
+    paths.retain(|p| {
+        !roots
+            .iter()
+            .any(|r| r != p && r.is_dir() && p.starts_with(r))
+    });
+    paths
+}
+
+/// Wait until the sources of the selected package, or of all the workspace members, change
+pub fn wait_for_source_change(
+    metadata: &Metadata,
+    package: &str,
+) -> impl std::future::Future<Output = ()> {
+    wait_for_change(
+        watched_paths(metadata, package),
+        Some(metadata.target_directory.clone().into_std_path_buf()),
+    )
+}
+
+/// The manifests of the workspace and its lockfile, which change the metadata
+pub fn manifest_paths(metadata: &Metadata) -> Vec<PathBuf> {
+    let root = metadata.workspace_root.as_std_path();
+    metadata
+        .workspace_packages()
+        .into_iter()
+        .map(|p| p.manifest_path.clone().into_std_path_buf())
+        .chain([root.join("Cargo.toml"), root.join("Cargo.lock")])
+        .unique()
+        .collect()
+}
+
+/// The modification time of all the files under the given paths, except under `ignored`
+fn snapshot(paths: &[PathBuf], ignored: Option<&Path>) -> HashMap<PathBuf, SystemTime> {
+    fn visit(path: &Path, ignored: Option<&Path>, files: &mut HashMap<PathBuf, SystemTime>) {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return;
+        };
+        if metadata.is_dir() {
+            let Ok(entries) = std::fs::read_dir(path) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let path = entry.path();
+                // editors' temporary files, and the build directories of the workspace and of
+                // members built on their own
+                if name.to_string_lossy().starts_with('.')
+                    || name == "target"
+                    || Some(path.as_path()) == ignored
+                {
+                    continue;
+                }
+                visit(&path, ignored, files);
+            }
+        } else if let Ok(modified) = metadata.modified() {
+            files.insert(path.to_owned(), modified);
+        }
+    }
+    let mut files = HashMap::new();
+    for path in paths {
+        visit(path, ignored, &mut files);
+    }
+    files
+}
+
+/// Wait until a file is added, removed or modified under the given paths, except under `ignored`,
+/// which is the target directory
+pub async fn wait_for_change(paths: Vec<PathBuf>, ignored: Option<PathBuf>) {
+    let paths = Arc::new(paths);
+    let ignored = Arc::new(ignored);
+    // Walking the sources takes a while, it must not block the other futures of the worker
+    let snapshot = || {
+        let (paths, ignored) = (paths.clone(), ignored.clone());
+        async move {
+            tokio::task::spawn_blocking(move || snapshot(&paths, ignored.as_deref()))
+                .await
+                .unwrap_or_default()
+        }
+    };
+    let mut current = snapshot().await;
+    let mut changed = false;
+    loop {
+        tokio::time::sleep(if changed { DEBOUNCE } else { POLL_INTERVAL }).await;
+        let next = snapshot().await;
+        if next != current {
+            changed = true;
+            current = next;
+        } else if changed {
+            return;
+        }
+    }
+}